@@ -159,9 +159,13 @@ export class SpeedScene extends Phaser.Scene {
     // Update status text
     if (!state.game_started) {
       this.statusText.setText('Waiting for another player to join...');
-    } else if (state.winner) {
-      const isWinner = state.winner === state.player_id;
-      this.statusText.setText(isWinner ? 'You Win!' : 'You Lose!');
+    } else if (state.outcome) {
+      if (typeof state.outcome === 'object') {
+        const isWinner = state.outcome.Win === state.player_id;
+        this.statusText.setText(isWinner ? 'You Win!' : 'You Lose!');
+      } else {
+        this.statusText.setText(state.outcome === 'Draw' ? 'Draw!' : 'Stalemate - no moves left');
+      }
     } else {
       this.statusText.setText('Game in progress');
     }
@@ -174,7 +178,7 @@ export class SpeedScene extends Phaser.Scene {
     this.opponentDrawPileText.setText(`Opponent Draw Pile: ${state.opponent_draw_pile_count}`);
     
     // Show/hide new cards button
-    this.newCardsButton.setVisible(state.game_started && !state.winner);
+    this.newCardsButton.setVisible(state.game_started && !state.outcome);
     
     // Render cards
     this.renderCards();
@@ -227,7 +231,7 @@ export class SpeedScene extends Phaser.Scene {
   }
   
   private onCardClick(cardIndex: number) {
-    if (!this.gameState || !this.gameState.game_started || this.gameState.winner) {
+    if (!this.gameState || !this.gameState.game_started || this.gameState.outcome) {
       return;
     }
     
@@ -23,15 +23,30 @@ export class WebSocketService {
         if (this.connectionStatusCallback) {
           this.connectionStatusCallback(true);
         }
+        // The scene connects once its assets have loaded, so the game can be dealt straight away
+        this.sendReady();
       };
 
       this.socket.onmessage = (event) => {
         try {
           const raw = JSON.parse(event.data);
-          const gameState: PlayerView = this.parseGameState(raw);
-          console.log('Received game state:', gameState);
-          if (this.gameStateCallback) {
-            this.gameStateCallback(gameState);
+          switch (raw.type) {
+            case 'GameState': {
+              const gameState: PlayerView = this.parseGameState(raw);
+              console.log('Received game state:', gameState);
+              if (this.gameStateCallback) {
+                this.gameStateCallback(gameState);
+              }
+              break;
+            }
+            case 'Error':
+              console.warn('Server error:', raw.code, raw.message);
+              if (this.errorCallback) {
+                this.errorCallback(raw.message);
+              }
+              break;
+            default:
+              console.log('Received server message:', raw);
           }
         } catch (error) {
           console.error('Error parsing game state:', error);
@@ -82,11 +97,19 @@ export class WebSocketService {
     }
   }
 
+  // Tell the server this client is ready for the game to start
+  public sendReady(): void {
+    if (this.socket && this.socket.readyState === WebSocket.OPEN) {
+      this.socket.send(JSON.stringify('Ready'));
+    }
+  }
+
   // Play a card from the player's hand to a center pile
-  public playCard(cardIndex: number): void {
+  public playCard(cardIndex: number, pileIndex?: number): void {
     this.sendAction({
       PlayCard: {
         card_index: cardIndex,
+        pile_index: pileIndex,
       }
     });
   }
@@ -31,8 +31,26 @@ export interface Card {
 
 // Player actions
 export type PlayerAction = 
-  | { PlayCard: { card_index: number } }
-  | { RequestNewCenterCards: null };
+  | { PlayCard: { card_index: number; pile_index?: number } }
+  | { RequestNewCenterCards: null }
+  | { RequestRedeal: null }
+  | { ClearPile: { pile_index: number } };
+
+// How a finished game ended
+export type GameOutcome =
+  | { Win: string }
+  | "Draw"
+  | "Stalemate";
+
+// Where the current deal came from
+export interface GameProvenance {
+  seats: string[];
+  hand_size: number;
+  draw_pile_size: number;
+  center_piles: number;
+  suit_rule: string;
+  deck_commitment: string;
+}
 
 // Player view of the game state
 export interface PlayerView {
@@ -41,7 +59,20 @@ export interface PlayerView {
   draw_pile_count: number;
   opponent_hand_count: number;
   opponent_draw_pile_count: number;
+  // Name the opponent chose, if any
+  opponent_name: string | null;
+  // Points each player has in a scored match
+  score: number | null;
+  opponent_score: number | null;
   center_piles: Card[][];
+  playable_piles: boolean[];
+  opponent_requested_redeal: boolean;
   game_started: boolean;
+  sudden_death: boolean;
+  paused: boolean;
   winner: string | null;
+  outcome: GameOutcome | null;
+  game_provenance: GameProvenance | null;
+  // UTC date of the deal in daily challenge games, as YYYY-MM-DD
+  challenge_date: string | null;
 }
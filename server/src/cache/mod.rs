@@ -0,0 +1,159 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Bounded map that forgets its least recently used entry when full and drops entries older than
+// an optional time to live. Meant for the small per-player caches, so lookups of the oldest entry
+// are linear rather than kept in a separate ordering structure. Ages are measured on Tokio's
+// clock so that tests can move time along.
+pub struct LruCache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    capacity: usize,
+    ttl: Option<Duration>,
+    // Logical clock ordering entries by last use
+    clock: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+impl<K: Hash + Eq, V> LruCache<K, V> {
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        LruCache {
+            entries: HashMap::with_capacity(capacity),
+            capacity,
+            ttl,
+            clock: 0,
+        }
+    }
+    
+    // Look up a live entry, marking it as recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let expired = self.entries.get(key).is_some_and(|entry| self.is_expired(entry));
+        if expired {
+            self.entries.remove(key);
+            return None;
+        }
+        
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(&entry.value)
+    }
+    
+    // Insert or replace an entry, evicting expired entries and then the least recently used one if full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        
+        if !self.entries.contains_key(&key) {
+            self.remove_expired();
+            if self.entries.len() >= self.capacity {
+                self.evict_least_recently_used();
+            }
+        }
+        
+        self.clock += 1;
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    // Keep only the entries the predicate accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) {
+        self.entries.retain(|key, entry| keep(key, &entry.value));
+    }
+    
+    // Drop every entry that has outlived the time to live
+    pub fn remove_expired(&mut self) {
+        if let Some(ttl) = self.ttl {
+            self.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+        }
+    }
+    
+    fn is_expired(&self, entry: &Entry<V>) -> bool {
+        self.ttl.is_some_and(|ttl| entry.inserted_at.elapsed() >= ttl)
+    }
+    
+    fn evict_least_recently_used(&mut self) {
+        // Every use ticks the clock, so the oldest timestamp identifies exactly one entry
+        if let Some(oldest) = self.entries.values().map(|entry| entry.last_used).min() {
+            self.entries.retain(|_, entry| entry.last_used != oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn full_cache_evicts_the_least_recently_used_entry() {
+        let mut cache = LruCache::new(2, None);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        
+        // Reading "a" makes "b" the oldest
+        assert_eq!(cache.get("a"), Some(&1));
+        cache.insert("c", 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("c"), Some(&3));
+        
+        // Replacing an entry doesn't evict anything
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("c"), Some(&4));
+    }
+    
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut cache = LruCache::new(0, None);
+        cache.insert("a", 1);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get("a"), None);
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn entries_expire_after_their_time_to_live() {
+        let ttl = Duration::from_secs(60);
+        let mut cache = LruCache::new(4, Some(ttl));
+        cache.insert("a", 1);
+        tokio::time::advance(ttl / 2).await;
+        assert_eq!(cache.get("a"), Some(&1));
+        cache.insert("b", 2);
+        
+        // Using an entry doesn't extend its life
+        tokio::time::advance(ttl / 2).await;
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(&2));
+        
+        tokio::time::advance(ttl / 2).await;
+        cache.remove_expired();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+use log::warn;
+use std::str::FromStr;
+
+// Where settings are looked up by name: the process environment when running, a map in tests
+pub type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+// Look a setting up in the process environment
+pub fn process(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+// Read and parse a setting, using the default if missing or invalid
+pub fn var_or<T: FromStr>(lookup: Lookup, key: &str, default: T) -> T {
+    match lookup(key) {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            warn!("Invalid value {:?} for {}, using default", value, key);
+            default
+        }),
+        None => default,
+    }
+}
+
+// Read and parse an optional setting, ignoring it if invalid
+pub fn var_opt<T: FromStr>(lookup: Lookup, key: &str) -> Option<T> {
+    let value = lookup(key)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            warn!("Invalid value {:?} for {}, ignoring", value, key);
+            None
+        }
+    }
+}
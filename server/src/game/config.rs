@@ -0,0 +1,345 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use super::{DailySecret, Rank};
+use crate::env;
+
+// Game configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameConfig {
+    // Number of cards dealt to each player's hand
+    pub hand_size: usize,
+    // Number of cards dealt to each player's draw pile
+    pub draw_pile_size: usize,
+    // Number of center piles cards are played on
+    pub center_piles: usize,
+    // Highest rank in the deck; lower values strip the deck down to Ace through that rank
+    pub highest_rank: u8,
+    // Hard cap on the number of cards a hand may hold
+    pub max_hand_size: usize,
+    // Suit restriction applied on top of the rank rule
+    pub suit_rule: SuitRule,
+    // Whether players may pick their seat before the game starts
+    pub allow_seat_requests: bool,
+    // Whether connecting players are put in random free seats instead of the lowest one
+    pub randomize_seats: bool,
+    // Seed for shuffling, making games reproducible; random if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    // Whether flipped cards are assigned to random center piles instead of left to right
+    pub randomize_flip: bool,
+    // Whether new center cards may only be flipped when neither player can play
+    pub flip_only_when_stuck: bool,
+    // Whether player views include which center piles the player can play on
+    pub pile_hints: bool,
+    // How many mutually agreed redeals are allowed per game
+    pub max_redeals: u32,
+    // Whether the next successful play wins once the deck and redeals are exhausted
+    pub sudden_death: bool,
+    // Cards a player must play between clearing center piles; clearing is disabled if unset
+    pub clear_pile_cooldown: Option<u32>,
+    // Whether the game over message shows every player's remaining cards
+    pub reveal_on_game_over: bool,
+    // Flips in a row without a card being played before the game is called a draw; unlimited if unset
+    pub max_consecutive_flips: Option<u32>,
+    // Actions after which the game ends, won by whoever has the fewest cards left; unlimited if unset
+    pub max_moves: Option<u32>,
+    // Whether a game is called a draw as soon as no card left anywhere could ever be played
+    pub detect_dead_games: bool,
+    // Special effects triggered by playing cards of certain ranks; none in standard games
+    pub powers: Powers,
+    // How the deal is chosen
+    pub mode: GameMode,
+    // Mixed into daily challenge seeds so the deal can't be derived from the date; never sent
+    // to players
+    #[serde(skip)]
+    pub daily_secret: DailySecret,
+    // Games in a match between the same players, won by whoever wins a majority of them; one
+    // plays single games
+    pub best_of: u32,
+    // Points that win a scored match, in which each round's winner scores the cards their
+    // opponent has left; matches count rounds instead if unset
+    pub point_target: Option<u32>,
+}
+
+// Suit restriction for playing a card onto a center pile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SuitRule {
+    // Any suit may be played (standard rules)
+    #[default]
+    Any,
+    // The played card must match the suit of the pile's top card
+    SameSuitRequired,
+    // The played card must differ in suit from the pile's top card
+    DifferentSuitRequired,
+}
+
+impl FromStr for SuitRule {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "any" => Ok(SuitRule::Any),
+            "same" | "same_suit" => Ok(SuitRule::SameSuitRequired),
+            "different" | "different_suit" => Ok(SuitRule::DifferentSuitRequired),
+            _ => Err(format!("unknown suit rule: {}", s)),
+        }
+    }
+}
+
+// Effect a card of a powered rank has when it is played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Power {
+    // The opponent may not flip new center cards until the next flip
+    SkipOpponentFlip,
+    // The pile played on is cleared back under the deck, leaving only the played card
+    ResetPile,
+}
+
+impl FromStr for Power {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip_flip" | "skip_opponent_flip" => Ok(Power::SkipOpponentFlip),
+            "reset_pile" => Ok(Power::ResetPile),
+            _ => Err(format!("unknown power: {}", s)),
+        }
+    }
+}
+
+// Powers assigned to ranks, parsed from a list like `J=skip_flip,2=reset_pile`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Powers(pub BTreeMap<Rank, Power>);
+
+impl Powers {
+    // The power a card of this rank carries, if any
+    pub fn get(&self, rank: Rank) -> Option<Power> {
+        self.0.get(&rank).copied()
+    }
+}
+
+impl FromStr for Powers {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut powers = BTreeMap::new();
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (rank, power) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected rank=power, got: {}", entry))?;
+            let rank = Rank::from_symbol(rank.trim()).ok_or_else(|| format!("unknown rank: {}", rank))?;
+            powers.insert(rank, power.trim().parse()?);
+        }
+        Ok(Powers(powers))
+    }
+}
+
+// How a game's deal is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    // Shuffled from the configured seed, or a random one
+    #[default]
+    Casual,
+    // Shuffled from a seed derived from the UTC date the game starts on and the server's daily
+    // secret, so every game that day gets the same deal; overrides the configured seed
+    DailyChallenge,
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "casual" => Ok(GameMode::Casual),
+            "daily" | "daily_challenge" => Ok(GameMode::DailyChallenge),
+            _ => Err(format!("unknown game mode: {}", s)),
+        }
+    }
+}
+
+// Named starting points for a game's configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Preset {
+    // The default rules with a full deck
+    #[default]
+    Standard,
+    // Quick rounds: Ace through 7 only, three-card hands and a single center pile
+    Blitz,
+}
+
+impl Preset {
+    pub fn config(self) -> GameConfig {
+        match self {
+            Preset::Standard => GameConfig::default(),
+            // 28 cards: 12 per player and one on the pile leaves three to flip
+            Preset::Blitz => GameConfig {
+                hand_size: 3,
+                max_hand_size: 3,
+                draw_pile_size: 9,
+                center_piles: 1,
+                highest_rank: 7,
+                ..GameConfig::default()
+            },
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "standard" => Ok(Preset::Standard),
+            "blitz" => Ok(Preset::Blitz),
+            _ => Err(format!("unknown preset: {}", s)),
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            hand_size: 5,
+            draw_pile_size: 15,
+            center_piles: 2,
+            highest_rank: 13,
+            max_hand_size: 5,
+            suit_rule: SuitRule::Any,
+            allow_seat_requests: false,
+            randomize_seats: false,
+            seed: None,
+            randomize_flip: false,
+            flip_only_when_stuck: false,
+            pile_hints: false,
+            max_redeals: 0,
+            sudden_death: false,
+            clear_pile_cooldown: None,
+            reveal_on_game_over: false,
+            max_consecutive_flips: None,
+            max_moves: None,
+            detect_dead_games: false,
+            powers: Powers::default(),
+            mode: GameMode::Casual,
+            daily_secret: DailySecret::default(),
+            best_of: 1,
+            point_target: None,
+        }
+    }
+}
+
+impl GameConfig {
+    // Number of cards in the deck
+    pub fn deck_size(&self) -> usize {
+        4 * self.highest_rank as usize
+    }
+    
+    // The rules as players may see them during a game, with the seed hidden until it is revealed at game over
+    pub fn public(&self) -> GameConfig {
+        GameConfig {
+            seed: None,
+            ..self.clone()
+        }
+    }
+    
+    // Load the configuration from environment variables, falling back to the selected preset
+    pub fn from_env() -> Self {
+        Self::from_vars(&env::process)
+    }
+    
+    // Like `from_env`, reading each setting through `lookup` instead
+    pub fn from_vars(lookup: env::Lookup) -> Self {
+        let defaults = env::var_or(lookup, "SPEED_PRESET", Preset::Standard).config();
+        
+        let hand_size = env::var_or(lookup, "SPEED_HAND_SIZE", defaults.hand_size);
+        let draw_pile_size = env::var_or(lookup, "SPEED_DRAW_PILE_SIZE", defaults.draw_pile_size);
+        let center_piles = env::var_or(lookup, "SPEED_CENTER_PILES", defaults.center_piles);
+        let highest_rank = env::var_or(lookup, "SPEED_HIGHEST_RANK", defaults.highest_rank);
+        // The cap defaults to the deal size so hands never grow past the initial deal
+        let max_hand_size = env::var_or(lookup, "SPEED_MAX_HAND_SIZE", hand_size);
+        let suit_rule = env::var_or(lookup, "SPEED_SUIT_RULE", defaults.suit_rule);
+        let allow_seat_requests = env::var_or(lookup, "SPEED_ALLOW_SEAT_REQUESTS", defaults.allow_seat_requests);
+        let randomize_seats = env::var_or(lookup, "SPEED_RANDOMIZE_SEATS", defaults.randomize_seats);
+        let seed = env::var_opt(lookup, "SPEED_SEED").or(defaults.seed);
+        let randomize_flip = env::var_or(lookup, "SPEED_RANDOMIZE_FLIP", defaults.randomize_flip);
+        let flip_only_when_stuck = env::var_or(lookup, "SPEED_FLIP_ONLY_WHEN_STUCK", defaults.flip_only_when_stuck);
+        let pile_hints = env::var_or(lookup, "SPEED_PILE_HINTS", defaults.pile_hints);
+        let max_redeals = env::var_or(lookup, "SPEED_MAX_REDEALS", defaults.max_redeals);
+        let sudden_death = env::var_or(lookup, "SPEED_SUDDEN_DEATH", defaults.sudden_death);
+        let clear_pile_cooldown = env::var_opt(lookup, "SPEED_CLEAR_PILE_COOLDOWN").or(defaults.clear_pile_cooldown);
+        let reveal_on_game_over = env::var_or(lookup, "SPEED_REVEAL_ON_GAME_OVER", defaults.reveal_on_game_over);
+        // Limits of zero would end every game as soon as it is dealt, so they mean no limit
+        let max_consecutive_flips = env::var_opt(lookup, "SPEED_MAX_CONSECUTIVE_FLIPS")
+            .filter(|&limit| limit > 0)
+            .or(defaults.max_consecutive_flips);
+        let max_moves = env::var_opt(lookup, "SPEED_MAX_MOVES").filter(|&limit| limit > 0).or(defaults.max_moves);
+        let detect_dead_games = env::var_or(lookup, "SPEED_DETECT_DEAD_GAMES", defaults.detect_dead_games);
+        let powers = env::var_or(lookup, "SPEED_POWERS", defaults.powers);
+        let mode = env::var_or(lookup, "SPEED_MODE", defaults.mode);
+        let daily_secret = env::var_opt::<String>(lookup, "SPEED_DAILY_SECRET").filter(|secret| !secret.is_empty());
+        let daily_secret = match daily_secret {
+            Some(secret) => DailySecret::new(secret),
+            None => {
+                if mode == GameMode::DailyChallenge {
+                    warn!("SPEED_DAILY_SECRET is unset, so daily deals are only shared by games on this server until it restarts");
+                }
+                defaults.daily_secret
+            }
+        };
+        let best_of = env::var_or(lookup, "SPEED_BEST_OF", defaults.best_of).max(1);
+        let point_target = env::var_opt(lookup, "SPEED_POINT_TARGET")
+            .filter(|&target| target > 0)
+            .or(defaults.point_target);
+        
+        GameConfig {
+            hand_size,
+            draw_pile_size,
+            center_piles,
+            highest_rank,
+            max_hand_size,
+            suit_rule,
+            allow_seat_requests,
+            randomize_seats,
+            seed,
+            randomize_flip,
+            flip_only_when_stuck,
+            pile_hints,
+            max_redeals,
+            sudden_death,
+            clear_pile_cooldown,
+            reveal_on_game_over,
+            max_consecutive_flips,
+            max_moves,
+            detect_dead_games,
+            powers,
+            mode,
+            daily_secret,
+            best_of,
+            point_target,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn zero_limits_from_the_environment_mean_no_limit() {
+        let vars = BTreeMap::from([("SPEED_MAX_CONSECUTIVE_FLIPS", "0"), ("SPEED_MAX_MOVES", "0")]);
+        let config = GameConfig::from_vars(&|key| vars.get(key).map(|value| value.to_string()));
+        
+        assert_eq!(config.max_consecutive_flips, None);
+        assert_eq!(config.max_moves, None);
+    }
+    
+    #[test]
+    fn presets_are_chosen_by_name() {
+        assert_eq!("Blitz".parse::<Preset>(), Ok(Preset::Blitz));
+        assert_eq!("standard".parse::<Preset>(), Ok(Preset::Standard));
+        assert!("turbo".parse::<Preset>().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Server-side secret mixed into daily seeds, so nobody can work out the day's deal from the date
+// before the seed is revealed at game over. Kept out of the rules sent to players and out of logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DailySecret(String);
+
+impl DailySecret {
+    pub fn new(secret: String) -> Self {
+        DailySecret(secret)
+    }
+}
+
+// A fresh random secret, so daily deals are hidden even if none is configured; they then only
+// match between games run by the same server process
+impl Default for DailySecret {
+    fn default() -> Self {
+        let bytes: [u8; 16] = StdRng::from_entropy().gen();
+        DailySecret(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+impl fmt::Debug for DailySecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DailySecret(..)")
+    }
+}
+
+// The UTC calendar date of a point in time as `YYYY-MM-DD`
+pub fn utc_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Seed shared by every daily challenge game on a date: the first eight bytes of the SHA-256 of
+// the server's secret and the date
+pub fn daily_seed(secret: &DailySecret, date: &str) -> u64 {
+    let digest = Sha256::digest(format!("speed-daily:{}:{}", secret.0, date).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+// Convert days since the Unix epoch to a proleptic Gregorian (year, month, day), following
+// Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    
+    fn secret() -> DailySecret {
+        DailySecret::new("test-secret".to_string())
+    }
+    
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+    
+    #[test]
+    fn dates_roll_over_at_midnight_utc() {
+        // 2024-03-01 00:00:00 UTC, just after a leap day
+        let midnight = 1_709_251_200;
+        assert_eq!(utc_date(at(midnight - 1)), "2024-02-29");
+        assert_eq!(utc_date(at(midnight)), "2024-03-01");
+        assert_eq!(utc_date(UNIX_EPOCH), "1970-01-01");
+    }
+    
+    #[test]
+    fn seed_is_stable_within_a_day_and_changes_across_days() {
+        let midnight = 1_709_251_200;
+        let seed_at = |secs| daily_seed(&secret(), &utc_date(at(secs)));
+        assert_eq!(seed_at(midnight), seed_at(midnight + 86_399));
+        assert_ne!(seed_at(midnight - 1), seed_at(midnight));
+    }
+    
+    #[test]
+    fn seed_depends_on_the_secret() {
+        let other = DailySecret::new("another-secret".to_string());
+        assert_ne!(daily_seed(&secret(), "2024-03-01"), daily_seed(&other, "2024-03-01"));
+        assert_ne!(DailySecret::default(), DailySecret::default());
+        assert!(!format!("{:?}", secret()).contains("test-secret"));
+    }
+}
@@ -0,0 +1,91 @@
+use super::{create_deck, is_complete_deck, Card};
+
+// Builds a deck in a chosen order for `GameState::start_game_with_deck`, e.g. to set up a
+// specific deal. Cards are kept in dealing order: the first card is dealt first.
+#[derive(Debug, Clone, Default)]
+pub struct DeckBuilder {
+    cards: Vec<Card>,
+}
+
+impl DeckBuilder {
+    // Start from no cards
+    pub fn new() -> Self {
+        DeckBuilder::default()
+    }
+    
+    // Start from every card from Ace up to the highest rank, in suit then rank order
+    pub fn full(highest_rank: u8) -> Self {
+        DeckBuilder {
+            cards: create_deck(highest_rank),
+        }
+    }
+    
+    // Take every copy of a card out of the deck
+    pub fn remove(mut self, card: Card) -> Self {
+        self.cards.retain(|&c| c != card);
+        self
+    }
+    
+    // Add a card at the end, to be dealt last
+    pub fn push(mut self, card: Card) -> Self {
+        self.cards.push(card);
+        self
+    }
+    
+    // Add several cards at the end, in order
+    pub fn extend(mut self, cards: impl IntoIterator<Item = Card>) -> Self {
+        self.cards.extend(cards);
+        self
+    }
+    
+    // Move cards to the front in the order given, so they are dealt first; cards not yet in the
+    // deck are added
+    pub fn deal_first(mut self, cards: impl IntoIterator<Item = Card>) -> Self {
+        let front: Vec<Card> = cards.into_iter().collect();
+        self.cards.retain(|card| !front.contains(card));
+        self.cards.splice(0..0, front);
+        self
+    }
+    
+    // Whether the deck holds every card of a deck up to the highest rank exactly once, as
+    // `start_game_with_deck` requires
+    pub fn is_complete(&self, highest_rank: u8) -> bool {
+        is_complete_deck(&self.cards, highest_rank)
+    }
+    
+    // The cards in dealing order
+    pub fn build(self) -> Vec<Card> {
+        self.cards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn card(name: &str) -> Card {
+        name.parse().unwrap()
+    }
+    
+    #[test]
+    fn builder_edits_keep_the_order_given() {
+        let deck = DeckBuilder::new().push(card("AS")).extend([card("2H"), card("3D")]).build();
+        assert_eq!(deck, vec![card("AS"), card("2H"), card("3D")]);
+        
+        let deck = DeckBuilder::full(3).remove(card("2S")).deal_first([card("3C"), card("AH")]).build();
+        assert_eq!(deck.len(), 11);
+        assert_eq!(deck[..2], [card("3C"), card("AH")]);
+        assert!(!deck.contains(&card("2S")));
+        assert_eq!(deck.iter().filter(|&&c| c == card("3C")).count(), 1);
+    }
+    
+    #[test]
+    fn only_full_decks_are_complete() {
+        assert!(DeckBuilder::full(13).is_complete(13));
+        assert!(DeckBuilder::full(13).deal_first([card("KS")]).is_complete(13));
+        assert!(!DeckBuilder::full(13).is_complete(12));
+        assert!(!DeckBuilder::full(13).remove(card("AS")).is_complete(13));
+        assert!(!DeckBuilder::full(13).push(card("AS")).is_complete(13));
+        assert!(!DeckBuilder::new().is_complete(13));
+    }
+}
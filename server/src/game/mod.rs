@@ -1,9 +1,32 @@
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
 use uuid::Uuid;
 
+mod config;
+mod daily;
+mod deck;
+mod provenance;
+mod series;
+mod stats;
+#[cfg(test)]
+mod tests;
+
+pub use config::{GameConfig, GameMode, Power, Powers, Preset, SuitRule};
+pub use daily::{daily_seed, utc_date, DailySecret};
+pub use deck::DeckBuilder;
+pub use provenance::{deck_commitment, hidden_state_fingerprint, DealReveal, GameProvenance};
+pub use series::{Series, SeriesPlayer};
+pub use stats::{GameStats, PlayerStats};
+
+// Number of seats at a table
+pub const MAX_PLAYERS: usize = 2;
+
 // Card representation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Card {
@@ -11,6 +34,47 @@ pub struct Card {
     pub rank: Rank,
 }
 
+impl Card {
+    // Single-byte encoding of the card: suit index (hearts, diamonds, clubs, spades) times 13
+    // plus the rank value minus one, giving 0..52
+    pub fn to_byte(&self) -> u8 {
+        let suit_index = match self.suit {
+            Suit::Hearts => 0,
+            Suit::Diamonds => 1,
+            Suit::Clubs => 2,
+            Suit::Spades => 3,
+        };
+        suit_index * 13 + (self.rank as u8 - 1)
+    }
+    
+    // Decode a card from its single-byte encoding
+    pub fn from_byte(byte: u8) -> Option<Card> {
+        let suit = match byte / 13 {
+            0 => Suit::Hearts,
+            1 => Suit::Diamonds,
+            2 => Suit::Clubs,
+            3 => Suit::Spades,
+            _ => return None,
+        };
+        let rank = Rank::from_value(byte % 13 + 1)?;
+        Some(Card { suit, rank })
+    }
+    
+    // Check if this card can be played on top of another card under the given suit rule, in a deck
+    // whose ranks run from Ace up to `highest_rank`
+    pub fn can_play_on(&self, other: &Card, suit_rule: SuitRule, highest_rank: u8) -> bool {
+        if !self.rank.can_play_on(&other.rank, highest_rank) {
+            return false;
+        }
+        
+        match suit_rule {
+            SuitRule::Any => true,
+            SuitRule::SameSuitRequired => self.suit == other.suit,
+            SuitRule::DifferentSuitRequired => self.suit != other.suit,
+        }
+    }
+}
+
 // Card suits
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Suit {
@@ -21,7 +85,7 @@ pub enum Suit {
 }
 
 // Card ranks
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Rank {
     Ace = 1,
     Two = 2,
@@ -39,17 +103,53 @@ pub enum Rank {
 }
 
 impl Rank {
-    // Check if this rank can be played on top of another rank
-    pub fn can_play_on(&self, other: &Rank) -> bool {
+    // Look up a rank by its numeric value (Ace = 1, King = 13)
+    pub fn from_value(value: u8) -> Option<Rank> {
+        match value {
+            1 => Some(Rank::Ace),
+            2 => Some(Rank::Two),
+            3 => Some(Rank::Three),
+            4 => Some(Rank::Four),
+            5 => Some(Rank::Five),
+            6 => Some(Rank::Six),
+            7 => Some(Rank::Seven),
+            8 => Some(Rank::Eight),
+            9 => Some(Rank::Nine),
+            10 => Some(Rank::Ten),
+            11 => Some(Rank::Jack),
+            12 => Some(Rank::Queen),
+            13 => Some(Rank::King),
+            _ => None,
+        }
+    }
+    
+    // Look up a rank by the symbol it is written with in card names: A, 2 to 10, J, Q or K
+    pub fn from_symbol(symbol: &str) -> Option<Rank> {
+        match symbol.to_ascii_uppercase().as_str() {
+            "A" => Some(Rank::Ace),
+            "J" => Some(Rank::Jack),
+            "Q" => Some(Rank::Queen),
+            "K" => Some(Rank::King),
+            number => number
+                .parse::<u8>()
+                .ok()
+                .filter(|&value| (2..=10).contains(&value))
+                .and_then(Rank::from_value),
+        }
+    }
+    
+    // Check if this rank can be played on top of another rank, in a deck whose ranks run from Ace
+    // up to `highest_rank`
+    pub fn can_play_on(&self, other: &Rank, highest_rank: u8) -> bool {
         let self_val = *self as u8;
         let other_val = *other as u8;
         
         // In Speed, you can play a card that's one higher or one lower
-        // With wrapping (King can be played on Ace and vice versa)
-        if self_val == 1 && other_val == 13 {
+        // With wrapping (the highest rank, King in a full deck, can be played on Ace and vice versa)
+        if self_val == 1 && other_val == highest_rank {
             return true;
         }
-        if self_val == 13 && other_val == 1 {
+        if self_val == highest_rank && other_val == 1 {
             return true;
         }
         
@@ -57,42 +157,518 @@ impl Rank {
     }
 }
 
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Suit::Hearts => "H",
+            Suit::Diamonds => "D",
+            Suit::Clubs => "C",
+            Suit::Spades => "S",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rank::Ace => write!(f, "A"),
+            Rank::Jack => write!(f, "J"),
+            Rank::Queen => write!(f, "Q"),
+            Rank::King => write!(f, "K"),
+            _ => write!(f, "{}", *self as u8),
+        }
+    }
+}
+
+// Cards render as rank followed by suit, e.g. "QS" or "10H"
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.rank, self.suit)
+    }
+}
+
+// Error returned when a string can't be parsed as a card
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCardError(String);
+
+impl fmt::Display for ParseCardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid card {:?}: expected a rank (A, 2-10, J, Q, K) followed by a suit (H, D, C, S)", self.0)
+    }
+}
+
+impl std::error::Error for ParseCardError {}
+
+// Parses the `Display` form back into a card, e.g. "QS", "10H" or "AC"
+impl FromStr for Card {
+    type Err = ParseCardError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseCardError(s.to_string());
+        
+        let trimmed = s.trim();
+        let mut chars = trimmed.chars();
+        let suit = match chars.next_back().map(|c| c.to_ascii_uppercase()) {
+            Some('H') => Suit::Hearts,
+            Some('D') => Suit::Diamonds,
+            Some('C') => Suit::Clubs,
+            Some('S') => Suit::Spades,
+            _ => return Err(error()),
+        };
+        
+        let rank = Rank::from_symbol(chars.as_str()).ok_or_else(error)?;
+        
+        Ok(Card { suit, rank })
+    }
+}
+
 // Game state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameState {
     pub players: Vec<PlayerState>,
     pub center_piles: Vec<Vec<Card>>,
     pub deck: Vec<Card>,
-    pub game_started: bool,
     pub winner: Option<Uuid>,
+    pub config: GameConfig,
+    // Players asking to scrap the current deal and start over
+    pub redeal_requests: Vec<Uuid>,
+    // Number of redeals already granted in this game
+    pub redeals: u32,
+    // Players asking to play again once the game is over
+    pub rematch_requests: Vec<Uuid>,
+    // Whether the next successful play wins the game
+    pub sudden_death: bool,
+    // Center flips since the last card was played
+    pub consecutive_flips: u32,
+    // Actions applied so far in this game
+    pub moves: u32,
+    // Where the game is in its lifecycle, only changed through `transition_to`
+    phase: GamePhase,
+    #[serde(skip, default = "entropy_rng")]
+    rng: StdRng,
+    // Seed behind the shuffles, drawn at random if the config doesn't fix one
+    seed: u64,
+    // The current deal's deck in dealing order, kept secret until the game is over
+    dealt_deck: Vec<Card>,
+    // UTC date the seed was derived from in daily challenge games
+    challenge_date: Option<String>,
+    // Match this game is a round of, with the rounds won before it
+    series: Series,
 }
 
 // Player state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerState {
     pub id: Uuid,
+    pub seat: usize,
+    // Name shown to the opponent, if the player chose one
+    pub name: Option<String>,
     pub hand: Vec<Card>,
     pub draw_pile: VecDeque<Card>,
+    // Counters for post-game stats
+    pub cards_played: u32,
+    pub flips_requested: u32,
+    // Value of `cards_played` when the player last cleared a center pile, if they have
+    pub cards_played_at_last_clear: Option<u32>,
+    // Whether an opponent's power bars this player from flipping until the next flip
+    pub flip_blocked: bool,
+}
+
+impl PlayerState {
+    // Cards the player still has to play before clearing a pile; the cooldown runs from their
+    // last clear, so the first one is available straight away
+    fn plays_until_clear(&self, cooldown: u32) -> u32 {
+        self.cards_played_at_last_clear
+            .map_or(0, |at| cooldown.saturating_sub(self.cards_played - at))
+    }
 }
 
 // Player-specific view of the game state
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerView {
     pub player_id: Uuid,
     pub hand: Vec<Card>,
     pub draw_pile_count: usize,
     pub opponent_hand_count: usize,
     pub opponent_draw_pile_count: usize,
+    // Name the opponent chose, if any
+    pub opponent_name: Option<String>,
+    // Points each player has in a scored match, counting this game once it is over
+    pub score: Option<u32>,
+    pub opponent_score: Option<u32>,
     pub center_piles: Vec<Vec<Card>>,
+    // Whether the player can play on each center pile; empty unless pile hints are enabled
+    pub playable_piles: Vec<bool>,
+    pub opponent_requested_redeal: bool,
     pub game_started: bool,
+    pub sudden_death: bool,
+    pub paused: bool,
+    // Kept alongside `outcome` for clients that only understand wins
     pub winner: Option<Uuid>,
+    pub outcome: Option<GameOutcome>,
+    pub game_provenance: Option<GameProvenance>,
+    // UTC date of the deal in daily challenge games, as `YYYY-MM-DD`
+    pub challenge_date: Option<String>,
+    // Extra fields added by a view projector, sent alongside the regular ones
+    #[serde(flatten)]
+    pub extras: BTreeMap<String, serde_json::Value>,
+}
+
+impl PlayerView {
+    // Keep only the top `depth` cards of each center pile to save bandwidth; shorter piles are left whole
+    pub fn keep_pile_tops(&mut self, depth: usize) {
+        for pile in &mut self.center_piles {
+            if pile.len() > depth {
+                pile.drain(..pile.len() - depth);
+            }
+        }
+    }
+    
+    // View for a connected player who hasn't been seated in a game yet
+    pub fn waiting_for_opponent(player_id: Uuid) -> Self {
+        PlayerView {
+            player_id,
+            hand: Vec::new(),
+            draw_pile_count: 0,
+            opponent_hand_count: 0,
+            opponent_draw_pile_count: 0,
+            opponent_name: None,
+            score: None,
+            opponent_score: None,
+            center_piles: Vec::new(),
+            playable_piles: Vec::new(),
+            opponent_requested_redeal: false,
+            game_started: false,
+            sudden_death: false,
+            paused: false,
+            winner: None,
+            outcome: None,
+            game_provenance: None,
+            challenge_date: None,
+            extras: BTreeMap::new(),
+        }
+    }
+}
+
+// Anonymized view for observers such as stream overlays: only counts and pile tops, no player ids
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObserverView {
+    // Hand and draw pile counts per seat
+    pub seats: Vec<ObservedSeat>,
+    pub center_tops: Vec<Option<Card>>,
+    pub deck_count: usize,
+    pub game_started: bool,
+    pub sudden_death: bool,
+    // Seat of the winning player, if the game was won
+    pub winning_seat: Option<usize>,
+    pub game_over: bool,
+}
+
+// What a spectator sees: every center pile in full, but only counts of each seat's cards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpectatorView {
+    pub seats: Vec<ObservedSeat>,
+    pub center_piles: Vec<Vec<Card>>,
+    pub deck_count: usize,
+    pub game_started: bool,
+    pub sudden_death: bool,
+    pub paused: bool,
+    pub winning_seat: Option<usize>,
+    pub game_over: bool,
+}
+
+// Card counts for one seat in an `ObserverView` or `SpectatorView`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ObservedSeat {
+    pub seat: usize,
+    pub hand_count: usize,
+    pub draw_pile_count: usize,
+}
+
+// A player's remaining cards, revealed once the game is over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevealedCards {
+    pub player_id: Uuid,
+    pub hand: Vec<Card>,
+    pub draw_pile: Vec<Card>,
+}
+
+// What a player is currently allowed to do, for driving UI controls
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PlayerStatus {
+    // Whether flipping new center cards would currently succeed
+    pub can_flip: bool,
+    // Why a flip would be rejected, if the game is in progress and it would be
+    pub flip_rejection: Option<FlipRejection>,
+    // Whether the player may still ask for a redeal
+    pub can_redeal: bool,
+    pub opponent_requested_redeal: bool,
+    pub cooldowns: Cooldowns,
+}
+
+// Cards the player still has to play before each limited action is available again; `None` when
+// the action is disabled in this game
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Cooldowns {
+    pub clear_pile: Option<u32>,
 }
 
 // Actions a player can take
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PlayerAction {
-    PlayCard { card_index: usize },
+    // Without a pile index the card goes on the first center pile it can be played on
+    PlayCard {
+        card_index: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pile_index: Option<usize>,
+    },
+    RequestNewCenterCards,
+    RequestRedeal,
+    ClearPile { pile_index: usize },
+}
+
+impl PlayerAction {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            PlayerAction::PlayCard { .. } => ActionKind::PlayCard,
+            PlayerAction::RequestNewCenterCards => ActionKind::RequestNewCenterCards,
+            PlayerAction::RequestRedeal => ActionKind::RequestRedeal,
+            PlayerAction::ClearPile { .. } => ActionKind::ClearPile,
+        }
+    }
+}
+
+// Player actions without their arguments, for deciding where each is allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionKind {
+    PlayCard,
     RequestNewCenterCards,
+    RequestRedeal,
+    ClearPile,
+    SetName,
+    Ready,
+    Rematch,
+}
+
+impl ActionKind {
+    // The one table of which actions each phase accepts; every action is checked against it
+    // before it is applied
+    pub fn allowed_in(self, phase: GamePhase) -> bool {
+        match self {
+            ActionKind::PlayCard
+            | ActionKind::RequestNewCenterCards
+            | ActionKind::RequestRedeal
+            | ActionKind::ClearPile => phase == GamePhase::Playing,
+            // Players are named before they're dealt in, not halfway through
+            ActionKind::SetName => phase == GamePhase::Waiting,
+            // Only a game that hasn't been dealt is waiting on its players to get ready
+            ActionKind::Ready => matches!(phase, GamePhase::Waiting | GamePhase::Ready),
+            ActionKind::Rematch => phase == GamePhase::Over,
+        }
+    }
+}
+
+// How a finished game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    // A player won: they ran out of cards, their opponent forfeited, or they had fewest cards at the move limit
+    Win(Uuid),
+    // The game was ended without a winner by a rule
+    Draw,
+    // The deck is exhausted and neither player can make a move
+    Stalemate,
+}
+
+// Details of a successfully applied action
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionResult {
+    // Center pile a played card landed on
+    pub pile_index: Option<usize>,
+}
+
+// Why new center cards can't be flipped right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlipRejection {
+    // Nothing is left in the deck
+    DeckEmpty,
+    // The variant only allows flips when nobody can play, and the requester still has a move
+    PlayerCanPlay,
+    // The variant only allows flips when nobody can play, and the opponent still has a move
+    OpponentCanPlay,
+    // The opponent played a card whose power skips this player's next flip
+    Blocked,
+}
+
+impl FlipRejection {
+    // Machine-readable code, reported as the action error's code
+    pub fn code(&self) -> &'static str {
+        match self {
+            FlipRejection::DeckEmpty => "deck_empty",
+            FlipRejection::PlayerCanPlay => "player_can_play",
+            FlipRejection::OpponentCanPlay => "opponent_can_play",
+            FlipRejection::Blocked => "flip_blocked",
+        }
+    }
+    
+    fn message(&self) -> &'static str {
+        match self {
+            FlipRejection::DeckEmpty => "there are no cards left to flip",
+            FlipRejection::PlayerCanPlay => "you still have a legal move",
+            FlipRejection::OpponentCanPlay => "your opponent still has a legal move",
+            FlipRejection::Blocked => "your opponent's card made you skip this flip",
+        }
+    }
+}
+
+// Reasons an action can be rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionError {
+    ActionNotAllowedInPhase { phase: GamePhase, action: ActionKind },
+    PlayerNotFound,
+    InvalidCardIndex,
+    NoPlayablePile,
+    AmbiguousPile,
+    CannotPlayOnPile,
+    FlipRejected(FlipRejection),
+    RedealNotAllowed,
+    RedealAlreadyRequested,
+    ClearPileNotAllowed,
+    ClearPileOnCooldown,
+    PileAlreadyEmpty,
+    InvalidPileIndex,
+    RematchAlreadyRequested,
+}
+
+impl ActionError {
+    // Machine-readable code for reporting the error to clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            ActionError::ActionNotAllowedInPhase { .. } => "action_not_allowed_in_phase",
+            ActionError::PlayerNotFound => "player_not_found",
+            ActionError::InvalidCardIndex => "invalid_card_index",
+            ActionError::NoPlayablePile => "no_playable_pile",
+            ActionError::AmbiguousPile => "ambiguous_pile",
+            ActionError::CannotPlayOnPile => "cannot_play_on_pile",
+            ActionError::FlipRejected(reason) => reason.code(),
+            ActionError::RedealNotAllowed => "redeal_not_allowed",
+            ActionError::RedealAlreadyRequested => "redeal_already_requested",
+            ActionError::ClearPileNotAllowed => "clear_pile_not_allowed",
+            ActionError::ClearPileOnCooldown => "clear_pile_on_cooldown",
+            ActionError::PileAlreadyEmpty => "pile_already_empty",
+            ActionError::InvalidPileIndex => "invalid_pile_index",
+            ActionError::RematchAlreadyRequested => "rematch_already_requested",
+        }
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ActionError::ActionNotAllowedInPhase { phase, action } => {
+                return write!(f, "{:?} is not allowed while the game is {:?}", action, phase);
+            }
+            ActionError::PlayerNotFound => "the player is not in this game",
+            ActionError::InvalidCardIndex => "there is no card at that index",
+            ActionError::NoPlayablePile => "the card cannot be played on any center pile",
+            ActionError::AmbiguousPile => "the card can be played on more than one center pile; choose one",
+            ActionError::CannotPlayOnPile => "the card cannot be played on that center pile",
+            ActionError::FlipRejected(reason) => reason.message(),
+            ActionError::RedealNotAllowed => "no more redeals are allowed in this game",
+            ActionError::RedealAlreadyRequested => "a redeal has already been requested",
+            ActionError::ClearPileNotAllowed => "clearing center piles is not allowed in this game",
+            ActionError::ClearPileOnCooldown => "play more cards before clearing another pile",
+            ActionError::PileAlreadyEmpty => "that center pile is already empty",
+            ActionError::InvalidPileIndex => "there is no center pile at that index",
+            ActionError::RematchAlreadyRequested => "a rematch has already been requested",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+// Reasons a game can't be started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartError {
+    AlreadyStarted,
+    WrongPlayerCount,
+    InsufficientDeck,
+    InvalidDeck,
+    InvalidConfig,
+}
+
+impl StartError {
+    // Machine-readable code for reporting the error to clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            StartError::AlreadyStarted => "already_started",
+            StartError::WrongPlayerCount => "wrong_player_count",
+            StartError::InsufficientDeck => "insufficient_deck",
+            StartError::InvalidDeck => "invalid_deck",
+            StartError::InvalidConfig => "invalid_config",
+        }
+    }
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            StartError::AlreadyStarted => "the game has already started",
+            StartError::WrongPlayerCount => "the game needs exactly two players to start",
+            StartError::InsufficientDeck => "the configured hands and piles need more cards than the deck has",
+            StartError::InvalidDeck => "the deck must hold every card of the configured deck exactly once",
+            StartError::InvalidConfig => "the game needs at least one center pile and a highest rank from 2 to 13",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+// Lifecycle of a game, from filling the seats to the final result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamePhase {
+    // Seats are still open
+    Waiting,
+    // Every seat is filled and the game can be dealt
+    Ready,
+    // Cards have been dealt and actions are accepted
+    Playing,
+    // Play is suspended, e.g. while a disconnected player's seat is empty
+    Paused,
+    // The game has an outcome and accepts no more actions
+    Over,
+}
+
+impl GamePhase {
+    // Whether the game may move from this phase to another
+    pub fn can_transition_to(self, next: GamePhase) -> bool {
+        matches!(
+            (self, next),
+            (GamePhase::Waiting, GamePhase::Ready)
+                | (GamePhase::Ready, GamePhase::Waiting)
+                | (GamePhase::Ready, GamePhase::Playing)
+                | (GamePhase::Playing, GamePhase::Paused)
+                | (GamePhase::Paused, GamePhase::Playing)
+                | (GamePhase::Playing, GamePhase::Over)
+                | (GamePhase::Paused, GamePhase::Over)
+        )
+    }
+    
+    // Whether cards have been dealt, including games that are paused or over
+    pub fn has_started(self) -> bool {
+        matches!(self, GamePhase::Playing | GamePhase::Paused | GamePhase::Over)
+    }
+}
+
+// A phase change the lifecycle doesn't allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: GamePhase,
+    pub to: GamePhase,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the game cannot move from {:?} to {:?}", self.from, self.to)
+    }
 }
 
 // Command from a player
@@ -100,29 +676,127 @@ pub enum PlayerAction {
 pub struct GameCommand {
     pub player_id: Uuid,
     pub action: PlayerAction,
+    // How a play that doesn't name a pile is resolved
+    pub pile_selection: PileSelection,
+}
+
+// How the server resolves a play that doesn't name a center pile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PileSelection {
+    // Play on the first pile the card fits
+    #[default]
+    FirstLegal,
+    // Reject the play if the card fits more than one pile, so the client has to choose
+    Ask,
 }
 
 impl GameState {
     // Create a new game state
-    pub fn new() -> Self {
+    pub fn new(config: GameConfig) -> Self {
+        // Always shuffle from a known seed so the deal can be revealed and verified afterwards
+        let seed = config.seed.unwrap_or_else(|| entropy_rng().gen());
+        let rng = StdRng::seed_from_u64(seed);
+        let series = Series::new(config.best_of, config.point_target);
+        
         GameState {
             players: Vec::new(),
-            center_piles: vec![Vec::new(), Vec::new()],
-            deck: create_deck(),
-            game_started: false,
+            center_piles: vec![Vec::new(); config.center_piles],
+            // The deck is only built when the game starts, so idle games stay cheap
+            deck: Vec::new(),
             winner: None,
+            config,
+            redeal_requests: Vec::new(),
+            redeals: 0,
+            rematch_requests: Vec::new(),
+            sudden_death: false,
+            consecutive_flips: 0,
+            moves: 0,
+            phase: GamePhase::Waiting,
+            rng,
+            seed,
+            dealt_deck: Vec::new(),
+            challenge_date: None,
+            series,
         }
     }
     
     // Start the game
-    pub fn start_game(&mut self) {
-        if self.players.len() != 2 {
-            return;
+    pub fn start_game(&mut self) -> Result<(), StartError> {
+        self.check_can_start()?;
+        self.begin_play()?;
+        
+        if self.config.mode == GameMode::DailyChallenge {
+            self.use_daily_seed();
+        }
+        self.deal_fresh_deck();
+        self.settle_outcome();
+        Ok(())
+    }
+    
+    // Reseed from today's date. The date is fixed when the game starts, so a game still going
+    // at midnight UTC keeps its deal and only games started afterwards get the next day's.
+    fn use_daily_seed(&mut self) {
+        let date = utc_date(SystemTime::now());
+        self.seed = daily_seed(&self.config.daily_secret, &date);
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.challenge_date = Some(date);
+    }
+    
+    // Build, shuffle and deal a full deck
+    fn deal_fresh_deck(&mut self) {
+        self.deck = create_deck(self.config.highest_rank);
+        self.deck.shuffle(&mut self.rng);
+        
+        self.deal();
+    }
+    
+    // Start the game with a fixed deck instead of a shuffled one, e.g. to reproduce a reported game.
+    // Cards are dealt in the order given; the deck must hold every card of the configured deck.
+    pub fn start_game_with_deck(&mut self, deck: Vec<Card>) -> Result<(), StartError> {
+        self.check_can_start()?;
+        if !is_complete_deck(&deck, self.config.highest_rank) {
+            return Err(StartError::InvalidDeck);
         }
         
-        // Shuffle the deck
-        let mut rng = thread_rng();
-        self.deck.shuffle(&mut rng);
+        // Cards are dealt from the end of the deck
+        self.deck = deck.into_iter().rev().collect();
+        self.begin_play()?;
+        
+        self.deal();
+        self.settle_outcome();
+        Ok(())
+    }
+    
+    // Move a ready table into play
+    fn begin_play(&mut self) -> Result<(), StartError> {
+        self.transition_to(GamePhase::Playing).map_err(|_| StartError::AlreadyStarted)
+    }
+    
+    // Check that the table is ready and the configuration can be dealt from one deck
+    fn check_can_start(&self) -> Result<(), StartError> {
+        match self.phase {
+            GamePhase::Waiting => return Err(StartError::WrongPlayerCount),
+            GamePhase::Ready => {}
+            _ => return Err(StartError::AlreadyStarted),
+        }
+        if !(2..=13).contains(&self.config.highest_rank) || self.center_piles.is_empty() {
+            return Err(StartError::InvalidConfig);
+        }
+        
+        // Hands are dealt up to the cap, so a larger deal size doesn't use more cards
+        let hand_cards = self.config.hand_size.min(self.config.max_hand_size);
+        let cards_needed = MAX_PLAYERS * (hand_cards + self.config.draw_pile_size) + self.center_piles.len();
+        if cards_needed > self.config.deck_size() {
+            return Err(StartError::InsufficientDeck);
+        }
+        
+        Ok(())
+    }
+    
+    // Deal the deck out to players and center piles
+    fn deal(&mut self) {
+        // Remember the deck in dealing order for the provenance commitment
+        self.dealt_deck = self.deck.iter().rev().copied().collect();
         
         // Deal cards to players
         self.deal_cards();
@@ -130,22 +804,27 @@ impl GameState {
         // Deal initial center cards
         self.deal_center_cards();
         
-        self.game_started = true;
+        self.update_sudden_death();
     }
     
     // Deal cards to players
     fn deal_cards(&mut self) {
-        // Each player gets 5 cards in hand and 15 in draw pile
+        let max_hand_size = self.config.max_hand_size;
+        
+        // Each player gets a hand and a draw pile of the configured sizes
         for player in &mut self.players {
-            // Deal 5 cards to hand
-            for _ in 0..5 {
+            // Deal cards to hand, never exceeding the hand size cap
+            for _ in 0..self.config.hand_size {
+                if player.hand.len() >= max_hand_size {
+                    break;
+                }
                 if let Some(card) = self.deck.pop() {
                     player.hand.push(card);
                 }
             }
             
-            // Deal 15 cards to draw pile
-            for _ in 0..15 {
+            // Deal cards to draw pile
+            for _ in 0..self.config.draw_pile_size {
                 if let Some(card) = self.deck.pop() {
                     player.draw_pile.push_back(card);
                 }
@@ -162,94 +841,482 @@ impl GameState {
         }
     }
     
-    // Add a player to the game
+    // Add a player to the game in the lowest free seat, or a random one if the config asks for it
     pub fn add_player(&mut self, id: Uuid) -> bool {
-        if self.players.len() >= 2 {
+        let free_seats: Vec<usize> = (0..MAX_PLAYERS).filter(|&seat| self.seat_is_free(seat)).collect();
+        
+        // Drawn from the game's seeded generator, so a fixed seed seats players the same way every time
+        let seat = if self.config.randomize_seats {
+            free_seats.choose(&mut self.rng).copied()
+        } else {
+            free_seats.first().copied()
+        };
+        
+        match seat {
+            Some(seat) => self.add_player_to_seat(id, seat),
+            None => false,
+        }
+    }
+    
+    // Add a player to the game in a specific seat, failing if it is taken or the player is already seated
+    pub fn add_player_to_seat(&mut self, id: Uuid, seat: usize) -> bool {
+        if self.phase != GamePhase::Waiting || seat >= MAX_PLAYERS || !self.seat_is_free(seat) {
+            return false;
+        }
+        
+        // A duplicate id would leave the player facing themselves as the opponent
+        if self.players.iter().any(|p| p.id == id) {
             return false;
         }
         
         self.players.push(PlayerState {
             id,
+            seat,
+            name: None,
             hand: Vec::new(),
             draw_pile: VecDeque::new(),
+            cards_played: 0,
+            flips_requested: 0,
+            cards_played_at_last_clear: None,
+            flip_blocked: false,
         });
+        self.players.sort_by_key(|p| p.seat);
+        
+        if self.players.len() == MAX_PLAYERS {
+            self.transition_to(GamePhase::Ready).is_ok()
+        } else {
+            true
+        }
+    }
+    
+    // Set or clear the name a seated player is shown to their opponent by
+    pub fn set_player_name(&mut self, id: Uuid, name: Option<String>) -> bool {
+        match self.players.iter_mut().find(|p| p.id == id) {
+            Some(player) => {
+                player.name = name;
+                true
+            }
+            None => false,
+        }
+    }
+    
+    // Change the name a seated player chose, which is only allowed while seats are still open
+    pub fn rename_player(&mut self, id: Uuid, name: String) -> Result<(), ActionError> {
+        if !self.players.iter().any(|p| p.id == id) {
+            return Err(ActionError::PlayerNotFound);
+        }
+        if !ActionKind::SetName.allowed_in(self.phase) {
+            return Err(ActionError::ActionNotAllowedInPhase { phase: self.phase, action: ActionKind::SetName });
+        }
+        
+        self.set_player_name(id, Some(name));
+        Ok(())
+    }
+    
+    // Give a departed player's seat in a paused game to someone else, along with the cards,
+    // requests and match standing that go with it
+    pub fn hand_over_seat(&mut self, from: Uuid, to: Uuid) -> bool {
+        if self.phase != GamePhase::Paused || self.players.iter().any(|p| p.id == to) {
+            return false;
+        }
+        let player = match self.players.iter_mut().find(|p| p.id == from) {
+            Some(player) => player,
+            None => return false,
+        };
         
+        player.id = to;
+        player.name = None;
+        for id in self.redeal_requests.iter_mut().filter(|id| **id == from) {
+            *id = to;
+        }
+        self.series.hand_over(from, to);
         true
     }
     
-    // Process a command from a player
-    pub fn process_command(&mut self, command: GameCommand) {
-        if !self.game_started || self.winner.is_some() {
-            return;
+    // Take a player out of a game that hasn't been dealt yet, reopening their seat
+    pub fn remove_player(&mut self, id: Uuid) -> bool {
+        if self.phase.has_started() || !self.players.iter().any(|p| p.id == id) {
+            return false;
+        }
+        
+        self.players.retain(|p| p.id != id);
+        if self.phase == GamePhase::Ready {
+            self.transition_to(GamePhase::Waiting).is_ok()
+        } else {
+            true
+        }
+    }
+    
+    // Move an already-seated player to another free seat before the game starts
+    pub fn request_seat(&mut self, id: Uuid, seat: usize) -> bool {
+        if !self.config.allow_seat_requests || self.phase.has_started() || seat >= MAX_PLAYERS {
+            return false;
+        }
+        
+        let player_index = match self.players.iter().position(|p| p.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        if self.players[player_index].seat == seat {
+            return true;
+        }
+        if !self.seat_is_free(seat) {
+            return false;
         }
         
+        self.players[player_index].seat = seat;
+        self.players.sort_by_key(|p| p.seat);
+        
+        true
+    }
+    
+    // Check whether no player currently occupies a seat
+    fn seat_is_free(&self, seat: usize) -> bool {
+        self.players.iter().all(|p| p.seat != seat)
+    }
+    
+    // Process a command from a player, returning an error if it changed nothing.
+    //
+    // Commands are applied one at a time against the current board, so when two plays target
+    // the same pile the first one processed wins it and the second is validated against the
+    // new top card rather than the board its sender saw; if it no longer fits it is rejected.
+    pub fn process_command(&mut self, command: GameCommand) -> Result<ActionResult, ActionError> {
+        // A stale action can arrive after its sender was removed from the game
+        if !self.players.iter().any(|p| p.id == command.player_id) {
+            return Err(ActionError::PlayerNotFound);
+        }
+        let action = command.action.kind();
+        if !action.allowed_in(self.phase) {
+            return Err(ActionError::ActionNotAllowedInPhase { phase: self.phase, action });
+        }
+        
+        let mut result = ActionResult::default();
+        
         match command.action {
-            PlayerAction::PlayCard { card_index } => {
-                self.play_card(command.player_id, card_index);
+            PlayerAction::PlayCard { card_index, pile_index } => {
+                let sudden_death = self.sudden_death;
+                result.pile_index = Some(self.play_card(
+                    command.player_id,
+                    card_index,
+                    pile_index,
+                    command.pile_selection,
+                )?);
+                self.consecutive_flips = 0;
+                
+                // In sudden death any successful play ends the game, even one whose power put
+                // cards back under the deck
+                if sudden_death {
+                    self.winner = Some(command.player_id);
+                }
             }
             PlayerAction::RequestNewCenterCards => {
-                self.request_new_center_cards();
+                self.request_new_center_cards(command.player_id)?;
+                self.consecutive_flips += 1;
+                
+                if let Some(player) = self.players.iter_mut().find(|p| p.id == command.player_id) {
+                    player.flips_requested += 1;
+                }
+            }
+            PlayerAction::RequestRedeal => {
+                self.request_redeal(command.player_id)?;
+            }
+            PlayerAction::ClearPile { pile_index } => {
+                self.clear_pile(command.player_id, pile_index)?;
             }
         }
         
+        self.moves += 1;
+        
         // Check for a winner
-        self.check_winner();
+        if self.winner.is_none() {
+            self.check_winner();
+        }
+        
+        self.update_sudden_death();
+        self.settle_outcome();
+        
+        Ok(result)
     }
     
-    // Play a card from a player's hand to a center pile
-    fn play_card(&mut self, player_id: Uuid, card_index: usize) {
-        // Find the player
-        let player_index = self.players.iter().position(|p| p.id == player_id);
-        if player_index.is_none() {
-            return;
-        }
-        let player_index = player_index.unwrap();
+    // Check whether a player could play a card from their hand, without changing anything,
+    // returning the pile it would go on. Without a pile index the first legal pile is chosen.
+    pub fn validate_move(
+        &self,
+        player_id: Uuid,
+        card_index: usize,
+        pile_index: Option<usize>,
+    ) -> Result<usize, ActionError> {
+        self.validate_move_with(player_id, card_index, pile_index, PileSelection::FirstLegal)
+    }
+    
+    // Like `validate_move`, resolving a play without a pile index as the selection mode says
+    pub fn validate_move_with(
+        &self,
+        player_id: Uuid,
+        card_index: usize,
+        pile_index: Option<usize>,
+        selection: PileSelection,
+    ) -> Result<usize, ActionError> {
+        let player = self
+            .players
+            .iter()
+            .find(|p| p.id == player_id)
+            .ok_or(ActionError::PlayerNotFound)?;
         
-        // Check if the indices are valid
-        if card_index >= self.players[player_index].hand.len() {
-            return;
-        }
+        let card = player.hand.get(card_index).ok_or(ActionError::InvalidCardIndex)?;
         
-        // Get the card and the pile
-        let card = self.players[player_index].hand[card_index];
-
-        // Find a valid pile to play the card
-        let mut selected_pile_index = None;
-        for (i, pile) in self.center_piles.iter().enumerate() {
-            if pile.is_empty() || card.rank.can_play_on(&pile.last().unwrap().rank) {
-                selected_pile_index = Some(i);
-                break;
+        match pile_index {
+            Some(pile_index) => {
+                let pile = self.center_piles.get(pile_index).ok_or(ActionError::InvalidPileIndex)?;
+                if !self.can_play_on_pile(card, pile) {
+                    return Err(ActionError::CannotPlayOnPile);
+                }
+                Ok(pile_index)
+            }
+            None => {
+                let mut legal = (0..self.center_piles.len())
+                    .filter(|&index| self.can_play_on_pile(card, &self.center_piles[index]));
+                let first = legal.next().ok_or(ActionError::NoPlayablePile)?;
+                if selection == PileSelection::Ask && legal.next().is_some() {
+                    return Err(ActionError::AmbiguousPile);
+                }
+                Ok(first)
             }
         }
-
-        let pile_index = match selected_pile_index {
-            Some(index) => index,
-            None => return,
-        };
+    }
+    
+    // Play a card from a player's hand to a center pile, returning the pile it was played on
+    fn play_card(
+        &mut self,
+        player_id: Uuid,
+        card_index: usize,
+        pile_index: Option<usize>,
+        selection: PileSelection,
+    ) -> Result<usize, ActionError> {
+        let pile_index = self.validate_move_with(player_id, card_index, pile_index, selection)?;
+        let player_index = self
+            .players
+            .iter()
+            .position(|p| p.id == player_id)
+            .ok_or(ActionError::PlayerNotFound)?;
+        let card = self.players[player_index].hand[card_index];
         
         // Play the card
         self.players[player_index].hand.remove(card_index);
         self.center_piles[pile_index].push(card);
+        self.players[player_index].cards_played += 1;
+        
+        if let Some(power) = self.config.powers.get(card.rank) {
+            self.apply_power(power, player_id, pile_index);
+        }
         
         // Draw a new card if available
-        if let Some(new_card) = self.players[player_index].draw_pile.pop_front() {
-            self.players[player_index].hand.push(new_card);
+        self.draw_card(player_index);
+        
+        Ok(pile_index)
+    }
+    
+    // Apply the effect of a powered card that was just played on a center pile
+    fn apply_power(&mut self, power: Power, player_id: Uuid, pile_index: usize) {
+        match power {
+            Power::SkipOpponentFlip => {
+                for opponent in self.players.iter_mut().filter(|p| p.id != player_id) {
+                    opponent.flip_blocked = true;
+                }
+            }
+            Power::ResetPile => {
+                // Like a cleared pile, the cards underneath go to the bottom of the deck
+                let pile = &mut self.center_piles[pile_index];
+                let beneath: Vec<Card> = pile.drain(..pile.len() - 1).collect();
+                self.deck.splice(0..0, beneath);
+                self.update_sudden_death();
+            }
+        }
+    }
+    
+    // Move the top card of a player's draw pile into their hand, respecting the hand size cap
+    fn draw_card(&mut self, player_index: usize) -> bool {
+        let player = &mut self.players[player_index];
+        if player.hand.len() >= self.config.max_hand_size {
+            return false;
+        }
+        
+        match player.draw_pile.pop_front() {
+            Some(card) => {
+                player.hand.push(card);
+                true
+            }
+            None => false,
         }
     }
     
     // Request new center cards when no moves are possible
-    fn request_new_center_cards(&mut self) {
-        // Check if there are cards left in the deck
-        if self.deck.is_empty() {
-            return;
+    fn request_new_center_cards(&mut self, player_id: Uuid) -> Result<(), ActionError> {
+        self.check_flip(player_id).map_err(ActionError::FlipRejected)?;
+        
+        // Deal new center cards, left to right unless the variant randomizes the piles
+        let mut pile_order: Vec<usize> = (0..self.center_piles.len()).collect();
+        if self.config.randomize_flip {
+            pile_order.shuffle(&mut self.rng);
         }
         
-        // Deal new center cards
-        for pile in &mut self.center_piles {
+        for pile_index in pile_order {
             if let Some(card) = self.deck.pop() {
-                pile.push(card);
+                self.center_piles[pile_index].push(card);
             }
         }
+        
+        // A skipped flip only lasts until the next one
+        for player in &mut self.players {
+            player.flip_blocked = false;
+        }
+        
+        Ok(())
+    }
+    
+    // Move a center pile back under the deck, leaving it empty so any card can be played on it
+    fn clear_pile(&mut self, player_id: Uuid, pile_index: usize) -> Result<(), ActionError> {
+        let cooldown = self.config.clear_pile_cooldown.ok_or(ActionError::ClearPileNotAllowed)?;
+        
+        let player_index = self
+            .players
+            .iter()
+            .position(|p| p.id == player_id)
+            .ok_or(ActionError::PlayerNotFound)?;
+        match self.center_piles.get(pile_index) {
+            None => return Err(ActionError::InvalidPileIndex),
+            Some(pile) if pile.is_empty() => return Err(ActionError::PileAlreadyEmpty),
+            Some(_) => {}
+        }
+        
+        // Players must play enough cards between clears
+        let player = &mut self.players[player_index];
+        if player.plays_until_clear(cooldown) > 0 {
+            return Err(ActionError::ClearPileOnCooldown);
+        }
+        player.cards_played_at_last_clear = Some(player.cards_played);
+        
+        // Cleared cards go to the bottom of the deck, since flips are dealt from the end
+        let cleared: Vec<Card> = self.center_piles[pile_index].drain(..).collect();
+        self.deck.splice(0..0, cleared);
+        self.update_sudden_death();
+        
+        Ok(())
+    }
+    
+    // Check whether a player could flip new center cards right now
+    fn check_flip(&self, player_id: Uuid) -> Result<(), FlipRejection> {
+        if self.deck.is_empty() {
+            return Err(FlipRejection::DeckEmpty);
+        }
+        if self.players.iter().any(|p| p.id == player_id && p.flip_blocked) {
+            return Err(FlipRejection::Blocked);
+        }
+        
+        if self.config.flip_only_when_stuck {
+            if self.find_playable_card(player_id).is_some() {
+                return Err(FlipRejection::PlayerCanPlay);
+            }
+            let opponent_can_play = self
+                .players
+                .iter()
+                .filter(|p| p.id != player_id)
+                .any(|p| self.find_playable_card(p.id).is_some());
+            if opponent_can_play {
+                return Err(FlipRejection::OpponentCanPlay);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Record a player's request to redeal, redealing once every player has asked
+    fn request_redeal(&mut self, player_id: Uuid) -> Result<(), ActionError> {
+        if !self.players.iter().any(|p| p.id == player_id) {
+            return Err(ActionError::PlayerNotFound);
+        }
+        if self.redeals >= self.config.max_redeals {
+            return Err(ActionError::RedealNotAllowed);
+        }
+        // Repeating a request changes nothing, so it is rejected rather than re-broadcast
+        if self.redeal_requests.contains(&player_id) {
+            return Err(ActionError::RedealAlreadyRequested);
+        }
+        
+        self.redeal_requests.push(player_id);
+        
+        if self.players.iter().all(|p| self.redeal_requests.contains(&p.id)) {
+            self.redeal();
+        }
+        
+        Ok(())
+    }
+    
+    // The match this game is a round of, counting this game's result once it is over
+    pub fn series(&self) -> Series {
+        let mut series = self.series.clone();
+        if let Some(outcome) = self.outcome() {
+            let cards_left = self.players.iter().map(|p| (p.id, (p.hand.len() + p.draw_pile.len()) as u32));
+            series.record(cards_left, outcome);
+        }
+        series
+    }
+    
+    // Carry on the match a finished game was a round of, making this game the next round or a
+    // replay of a drawn one, if the same players are seated and neither has won it yet. Returns
+    // whether the match continues.
+    pub fn continue_series(&mut self, previous: &GameState) -> bool {
+        let series = previous.series();
+        let player_ids: Vec<Uuid> = self.players.iter().map(|p| p.id).collect();
+        let outcome = match previous.outcome() {
+            Some(outcome) => outcome,
+            None => return false,
+        };
+        if !series.is_match() || series.winner().is_some() {
+            return false;
+        }
+        if !series.is_between(&player_ids) {
+            return false;
+        }
+        
+        self.series = series.next_round(outcome);
+        true
+    }
+    
+    // Record a player's request to play again after the game is over. Returns whether every
+    // player has now asked; the rematch itself is a new game, dealt by whoever owns this one.
+    pub fn request_rematch(&mut self, player_id: Uuid) -> Result<bool, ActionError> {
+        if !self.players.iter().any(|p| p.id == player_id) {
+            return Err(ActionError::PlayerNotFound);
+        }
+        if !ActionKind::Rematch.allowed_in(self.phase) {
+            return Err(ActionError::ActionNotAllowedInPhase { phase: self.phase, action: ActionKind::Rematch });
+        }
+        if self.rematch_requests.contains(&player_id) {
+            return Err(ActionError::RematchAlreadyRequested);
+        }
+        
+        self.rematch_requests.push(player_id);
+        Ok(self.players.iter().all(|p| self.rematch_requests.contains(&p.id)))
+    }
+    
+    // Collect every card and deal a fresh game, keeping players in their seats
+    fn redeal(&mut self) {
+        for player in &mut self.players {
+            player.hand.clear();
+            player.draw_pile.clear();
+            player.flip_blocked = false;
+        }
+        for pile in &mut self.center_piles {
+            pile.clear();
+        }
+        
+        self.redeal_requests.clear();
+        self.redeals += 1;
+        self.sudden_death = false;
+        self.consecutive_flips = 0;
+        
+        self.deal_fresh_deck();
     }
     
     // Check if there's a winner
@@ -262,22 +1329,381 @@ impl GameState {
         }
     }
     
+    // Enter sudden death once nothing is left to flip and no redeals remain, if the variant allows
+    // it, and leave it again if cards are put back under the deck before anyone wins
+    fn update_sudden_death(&mut self) {
+        if self.winner.is_some() {
+            return;
+        }
+        self.sudden_death = self.config.sudden_death
+            && self.phase.has_started()
+            && self.deck.is_empty()
+            && self.redeals >= self.config.max_redeals;
+    }
+    
+    // End a game in progress by awarding it to the forfeiting player's opponent
+    pub fn forfeit(&mut self, player_id: Uuid) -> bool {
+        if !self.phase.can_transition_to(GamePhase::Over) || !self.players.iter().any(|p| p.id == player_id) {
+            return false;
+        }
+        
+        match self.players.iter().find(|p| p.id != player_id) {
+            Some(opponent) => {
+                self.winner = Some(opponent.id);
+                self.settle_outcome();
+                true
+            }
+            None => false,
+        }
+    }
+    
+    // Per-player stats for the game so far
+    pub fn stats(&self) -> GameStats {
+        GameStats {
+            players: self
+                .players
+                .iter()
+                .map(|p| PlayerStats::new(p.id, p.cards_played, p.flips_requested))
+                .collect(),
+        }
+    }
+    
+    // Every player's remaining cards, only available once the game is over and the variant reveals them
+    pub fn revealed_cards(&self) -> Option<Vec<RevealedCards>> {
+        if !self.config.reveal_on_game_over || !self.is_game_over() {
+            return None;
+        }
+        
+        Some(
+            self.players
+                .iter()
+                .map(|p| RevealedCards {
+                    player_id: p.id,
+                    hand: p.hand.clone(),
+                    draw_pile: p.draw_pile.iter().copied().collect(),
+                })
+                .collect(),
+        )
+    }
+    
+    // Where the current deal came from, once the game has started
+    pub fn provenance(&self) -> Option<GameProvenance> {
+        if !self.phase.has_started() {
+            return None;
+        }
+        
+        Some(GameProvenance {
+            seats: self.players.iter().map(|p| p.id).collect(),
+            hand_size: self.config.hand_size,
+            draw_pile_size: self.config.draw_pile_size,
+            center_piles: self.center_piles.len(),
+            suit_rule: self.config.suit_rule,
+            deck_commitment: deck_commitment(self.seed, &self.dealt_deck),
+        })
+    }
+    
+    // Stable hash of every hidden card position: the deck order and each player's hand and draw pile.
+    // Logged at the start and end of a game so an auditor can check nothing moved outside the rules.
+    pub fn deck_fingerprint(&self) -> String {
+        hidden_state_fingerprint(
+            &self.deck,
+            self.players
+                .iter()
+                .map(|p| (p.hand.as_slice(), p.draw_pile.iter().copied().collect())),
+        )
+    }
+    
+    // The seed and deck behind the current deal, only available once the game is over
+    pub fn deal_reveal(&self) -> Option<DealReveal> {
+        if !self.is_game_over() {
+            return None;
+        }
+        
+        Some(DealReveal {
+            seed: self.seed,
+            deck: self.dealt_deck.clone(),
+        })
+    }
+    
+    // Where the game is in its lifecycle
+    pub fn phase(&self) -> GamePhase {
+        self.phase
+    }
+    
+    // Move the game to another phase, rejecting changes the lifecycle doesn't allow
+    pub fn transition_to(&mut self, next: GamePhase) -> Result<(), InvalidTransition> {
+        if !self.phase.can_transition_to(next) {
+            return Err(InvalidTransition { from: self.phase, to: next });
+        }
+        
+        self.phase = next;
+        Ok(())
+    }
+    
+    // End the game once the board has an outcome, recording the winner of games won by a rule
+    // like the move limit as well as by emptying a hand
+    fn settle_outcome(&mut self) {
+        if !self.phase.can_transition_to(GamePhase::Over) {
+            return;
+        }
+        
+        if let Some(outcome) = self.detect_outcome() {
+            if let GameOutcome::Win(winner) = outcome {
+                self.winner = Some(winner);
+            }
+            self.phase = GamePhase::Over;
+        }
+    }
+    
+    // Check whether the game has reached a terminal state
+    pub fn is_game_over(&self) -> bool {
+        self.phase == GamePhase::Over
+    }
+    
+    // How the game ended, or `None` while it is still being played
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        if !self.is_game_over() {
+            return None;
+        }
+        
+        self.detect_outcome()
+    }
+    
+    // The outcome the board currently implies, checked after every change to it
+    fn detect_outcome(&self) -> Option<GameOutcome> {
+        if let Some(winner) = self.winner {
+            return Some(GameOutcome::Win(winner));
+        }
+        
+        // Players who only ever flip would otherwise keep the game going forever
+        if self
+            .config
+            .max_consecutive_flips
+            .is_some_and(|limit| self.consecutive_flips >= limit)
+        {
+            return Some(GameOutcome::Draw);
+        }
+        
+        // Games that run too long go to whoever is closest to finishing, or are drawn on a tie
+        if self.config.max_moves.is_some_and(|limit| self.moves >= limit) {
+            return Some(self.fewest_cards_outcome());
+        }
+        
+        // Stuck with nothing left to flip, unless the board can still be changed another way
+        if self.phase.has_started() && self.deck.is_empty() && !self.any_move_possible() && !self.reset_possible() {
+            return Some(GameOutcome::Stalemate);
+        }
+        
+        // Flipping through the rest of the deck can't help if none of it unblocks anyone
+        if self.config.detect_dead_games && self.phase.has_started() && !self.progress_possible() {
+            return Some(GameOutcome::Draw);
+        }
+        
+        None
+    }
+    
+    // The player with the fewest cards left in hand and draw pile wins; a tie is a draw
+    fn fewest_cards_outcome(&self) -> GameOutcome {
+        let remaining = |p: &PlayerState| p.hand.len() + p.draw_pile.len();
+        let fewest = self.players.iter().map(remaining).min();
+        let mut leaders = self.players.iter().filter(|p| Some(remaining(p)) == fewest);
+        
+        match (leaders.next(), leaders.next()) {
+            (Some(leader), None) => GameOutcome::Win(leader.id),
+            _ => GameOutcome::Draw,
+        }
+    }
+    
+    // Whether any card could still be played at some point. Tops only change by flipping deck
+    // cards or by playing, so if no player's card fits a current top or any card still in the
+    // deck, nothing can ever be played, unless a pile can be cleared or the deck redealt.
+    fn progress_possible(&self) -> bool {
+        if self.reset_possible() {
+            return true;
+        }
+        
+        let (suit_rule, highest_rank) = (self.config.suit_rule, self.config.highest_rank);
+        self.players
+            .iter()
+            .flat_map(|p| p.hand.iter().chain(p.draw_pile.iter()))
+            .any(|card| {
+                self.center_piles.iter().any(|pile| self.can_play_on_pile(card, pile))
+                    || self.deck.iter().any(|top| card.can_play_on(top, suit_rule, highest_rank))
+            })
+    }
+    
+    // Whether the board can still be changed without playing a card, by redealing or clearing a
+    // pile. Playing is the only way to come off cooldown, so only a clear available now counts.
+    fn reset_possible(&self) -> bool {
+        if self.redeals < self.config.max_redeals {
+            return true;
+        }
+        
+        self.config.clear_pile_cooldown.is_some_and(|cooldown| {
+            self.players.iter().any(|p| p.plays_until_clear(cooldown) == 0)
+        })
+    }
+    
+    // Check whether any player can play a card
+    fn any_move_possible(&self) -> bool {
+        self.players.iter().any(|p| self.find_playable_card(p.id).is_some())
+    }
+    
+    // Find the first card in a player's hand that can be played on any center pile
+    pub fn find_playable_card(&self, player_id: Uuid) -> Option<usize> {
+        let player = self.players.iter().find(|p| p.id == player_id)?;
+        
+        (0..player.hand.len()).find(|&card_index| self.validate_move(player_id, card_index, None).is_ok())
+    }
+    
+    // Check whether a card can be played on a center pile under the active rules
+    fn can_play_on_pile(&self, card: &Card, pile: &[Card]) -> bool {
+        match pile.last() {
+            Some(top) => card.can_play_on(top, self.config.suit_rule, self.config.highest_rank),
+            None => true,
+        }
+    }
+    
+    // For each center pile, whether the player holds a card that can be played on it
+    fn playable_piles(&self, player: &PlayerState) -> Vec<bool> {
+        (0..self.center_piles.len())
+            .map(|pile_index| {
+                (0..player.hand.len())
+                    .any(|card_index| self.validate_move(player.id, card_index, Some(pile_index)).is_ok())
+            })
+            .collect()
+    }
+    
+    // Render the full board as a human-readable, multi-line summary
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        
+        for (i, player) in self.players.iter().enumerate() {
+            lines.push(format!(
+                "Player {} ({}): hand [{}], draw pile {}",
+                i + 1,
+                player.id,
+                render_cards(&player.hand),
+                player.draw_pile.len()
+            ));
+        }
+        
+        let pile_tops = self
+            .center_piles
+            .iter()
+            .map(|pile| match pile.last() {
+                Some(card) => format!("{} ({} cards)", card, pile.len()),
+                None => "empty".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!("Center piles: {}", pile_tops));
+        lines.push(format!("Deck: {} cards remaining", self.deck.len()));
+        
+        match self.outcome() {
+            Some(GameOutcome::Win(winner)) => lines.push(format!("Winner: {}", winner)),
+            Some(GameOutcome::Draw) => lines.push("Result: draw".to_string()),
+            Some(GameOutcome::Stalemate) => lines.push("Result: stalemate".to_string()),
+            None => lines.push("Winner: none".to_string()),
+        }
+        
+        lines.join("\n")
+    }
+    
+    // What a seated player can currently do, or `None` if they aren't in the game
+    pub fn player_status(&self, player_id: Uuid) -> Option<PlayerStatus> {
+        let player = self.players.iter().find(|p| p.id == player_id)?;
+        let can_request_flip = ActionKind::RequestNewCenterCards.allowed_in(self.phase);
+        let flip_rejection = if can_request_flip { self.check_flip(player_id).err() } else { None };
+        
+        let clear_pile = self.config.clear_pile_cooldown.map(|cooldown| player.plays_until_clear(cooldown));
+        
+        Some(PlayerStatus {
+            can_flip: can_request_flip && flip_rejection.is_none(),
+            flip_rejection,
+            can_redeal: ActionKind::RequestRedeal.allowed_in(self.phase)
+                && self.redeals < self.config.max_redeals
+                && !self.redeal_requests.contains(&player_id),
+            opponent_requested_redeal: self.redeal_requests.iter().any(|&id| id != player_id),
+            cooldowns: Cooldowns { clear_pile },
+        })
+    }
+    
+    // Create an anonymized view for observers
+    pub fn create_observer_view(&self) -> ObserverView {
+        let seats = self
+            .players
+            .iter()
+            .map(|p| ObservedSeat {
+                seat: p.seat,
+                hand_count: p.hand.len(),
+                draw_pile_count: p.draw_pile.len(),
+            })
+            .collect();
+        
+        // Report the winner by seat so no player id reaches the overlay
+        let winning_seat = self
+            .winner
+            .and_then(|winner| self.players.iter().find(|p| p.id == winner))
+            .map(|p| p.seat);
+        
+        ObserverView {
+            seats,
+            center_tops: self.center_piles.iter().map(|pile| pile.last().copied()).collect(),
+            deck_count: self.deck.len(),
+            game_started: self.phase.has_started(),
+            sudden_death: self.sudden_death,
+            winning_seat,
+            game_over: self.is_game_over(),
+        }
+    }
+    
+    // Create the view sent to spectators, built on the observer view with the piles in full
+    pub fn create_spectator_view(&self) -> SpectatorView {
+        let observer = self.create_observer_view();
+        SpectatorView {
+            seats: observer.seats,
+            center_piles: self.center_piles.clone(),
+            deck_count: observer.deck_count,
+            game_started: observer.game_started,
+            sudden_death: observer.sudden_death,
+            paused: self.phase == GamePhase::Paused,
+            winning_seat: observer.winning_seat,
+            game_over: observer.game_over,
+        }
+    }
+    
     // Create a player-specific view of the game state
     pub fn create_player_view(&self, player_id: Uuid) -> PlayerView {
-        // Find the player
-        let player_index = self.players.iter().position(|p| p.id == player_id).unwrap_or(0);
+        // Find the player, falling back to a waiting view if they haven't been seated yet
+        let player_index = match self.players.iter().position(|p| p.id == player_id) {
+            Some(index) => index,
+            None => return PlayerView::waiting_for_opponent(player_id),
+        };
         
         // Get player info
         let player = &self.players[player_index];
         
-        // Get opponent info if there is one
-        let (opponent_hand_count, opponent_draw_pile_count) = if self.players.len() > 1 {
-            let opponent_index = if player_index == 0 { 1 } else { 0 };
-            let opponent = &self.players[opponent_index];
-            (opponent.hand.len(), opponent.draw_pile.len())
+        // Only counts of the opponent's cards are exposed; their hand and draw pile contents
+        // must never be copied into another player's view
+        let opponent = self.players.iter().find(|p| p.id != player_id);
+        let (opponent_hand_count, opponent_draw_pile_count) = opponent
+            .map(|opponent| (opponent.hand.len(), opponent.draw_pile.len()))
+            .unwrap_or((0, 0));
+        
+        // Let the player know if their opponent is waiting on them to agree to a redeal
+        let opponent_requested_redeal = self
+            .redeal_requests
+            .iter()
+            .any(|&id| id != player_id);
+        
+        let series = self.series();
+        
+        // Only compute hints when the assist is enabled
+        let playable_piles = if self.config.pile_hints {
+            self.playable_piles(player)
         } else {
-            // No opponent
-            (0, 0)
+            Vec::new()
         };
         
         PlayerView {
@@ -286,36 +1712,50 @@ impl GameState {
             draw_pile_count: player.draw_pile.len(),
             opponent_hand_count,
             opponent_draw_pile_count,
+            opponent_name: opponent.and_then(|opponent| opponent.name.clone()),
+            score: series.points(player_id),
+            opponent_score: opponent.and_then(|opponent| series.points(opponent.id)),
             center_piles: self.center_piles.clone(),
-            game_started: self.game_started,
+            playable_piles,
+            opponent_requested_redeal,
+            game_started: self.phase.has_started(),
+            sudden_death: self.sudden_death,
+            paused: self.phase == GamePhase::Paused,
             winner: self.winner,
+            outcome: self.outcome(),
+            game_provenance: self.provenance(),
+            challenge_date: self.challenge_date.clone(),
+            extras: BTreeMap::new(),
         }
     }
 }
 
-// Create a standard deck of 52 cards
-fn create_deck() -> Vec<Card> {
-    let mut deck = Vec::with_capacity(52);
+// Random number generator seeded from the operating system
+fn entropy_rng() -> StdRng {
+    StdRng::from_entropy()
+}
+
+// Render a sequence of cards separated by spaces
+pub(crate) fn render_cards<'a>(cards: impl IntoIterator<Item = &'a Card>) -> String {
+    cards
+        .into_iter()
+        .map(|card| card.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Check that a deck holds every card up to the highest rank exactly once
+pub fn is_complete_deck(deck: &[Card], highest_rank: u8) -> bool {
+    let full_deck = create_deck(highest_rank);
+    deck.len() == full_deck.len() && full_deck.iter().all(|card| deck.contains(card))
+}
+
+// Create a deck of every suit from Ace up to the highest rank; King gives the standard 52 cards
+fn create_deck(highest_rank: u8) -> Vec<Card> {
+    let mut deck = Vec::with_capacity(4 * highest_rank as usize);
     
     for &suit in &[Suit::Hearts, Suit::Diamonds, Suit::Clubs, Suit::Spades] {
-        for rank in 1..=13 {
-            let rank = match rank {
-                1 => Rank::Ace,
-                2 => Rank::Two,
-                3 => Rank::Three,
-                4 => Rank::Four,
-                5 => Rank::Five,
-                6 => Rank::Six,
-                7 => Rank::Seven,
-                8 => Rank::Eight,
-                9 => Rank::Nine,
-                10 => Rank::Ten,
-                11 => Rank::Jack,
-                12 => Rank::Queen,
-                13 => Rank::King,
-                _ => unreachable!(),
-            };
-            
+        for rank in (1..=highest_rank).filter_map(Rank::from_value) {
             deck.push(Card { suit, rank });
         }
     }
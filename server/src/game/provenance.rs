@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{render_cards, Card, SuitRule};
+
+// What a game was dealt from, for auditing a deal after the fact. The deck itself stays hidden
+// behind a commitment until the game is over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameProvenance {
+    // Player ids in seat order
+    pub seats: Vec<Uuid>,
+    pub hand_size: usize,
+    pub draw_pile_size: usize,
+    pub center_piles: usize,
+    pub suit_rule: SuitRule,
+    // Hex SHA-256 commitment to the seed and deck order of the current deal
+    pub deck_commitment: String,
+}
+
+// The seed and deck order behind a deal, revealed at game over so clients can check the commitment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DealReveal {
+    pub seed: u64,
+    // Cards in the order they were dealt
+    pub deck: Vec<Card>,
+}
+
+impl DealReveal {
+    // Check the revealed deal against a commitment received during the game
+    pub fn matches(&self, commitment: &str) -> bool {
+        deck_commitment(self.seed, &self.deck).eq_ignore_ascii_case(commitment)
+    }
+}
+
+// Fingerprint of hidden card positions: SHA-256 over the deck followed by each seat's hand and draw
+// pile, every section rendered like the deck commitment and prefixed with a label, as lowercase hex
+pub fn hidden_state_fingerprint<'a>(deck: &[Card], seats: impl IntoIterator<Item = (&'a [Card], Vec<Card>)>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("deck:{}", render_cards(deck)));
+    for (seat, (hand, draw_pile)) in seats.into_iter().enumerate() {
+        hasher.update(format!("|{}:hand:{}|{}:draw:{}", seat, render_cards(hand), seat, render_cards(&draw_pile)));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// Commitment to a deal: SHA-256 of the seed in decimal, a colon, then the dealt cards rendered like
+// "QS 10H AD" with single spaces, as lowercase hex
+pub fn deck_commitment(seed: u64, deck: &[Card]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}:{}", seed, render_cards(deck)));
+    format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::GameOutcome;
+
+// A match of several games between the same two players, each one a round. It is won by the
+// first player to win a majority of the rounds or, when scoring, to reach the point target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Series {
+    // Rounds in the match; the first player to win more than half of them takes it
+    pub best_of: u32,
+    // Points that win the match when scoring, which replaces counting rounds; not scored if unset
+    pub point_target: Option<u32>,
+    // Round being played, counting from one
+    pub round: u32,
+    // Each player's standing, in seat order
+    pub players: Vec<SeriesPlayer>,
+}
+
+// One player's standing in a `Series`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SeriesPlayer {
+    pub player_id: Uuid,
+    pub rounds_won: u32,
+    // Cards left to their opponents in the rounds they won
+    pub points: u32,
+}
+
+impl Series {
+    // The first round of a new match
+    pub fn new(best_of: u32, point_target: Option<u32>) -> Self {
+        Series {
+            best_of: best_of.max(1),
+            point_target: point_target.filter(|&target| target > 0),
+            round: 1,
+            players: Vec::new(),
+        }
+    }
+    
+    // Pass a player's standing on to whoever took over their seat
+    pub(super) fn hand_over(&mut self, from: Uuid, to: Uuid) {
+        for player in self.players.iter_mut().filter(|p| p.player_id == from) {
+            player.player_id = to;
+        }
+    }
+    
+    // Rounds a player must win to take the match
+    pub fn rounds_to_win(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+    
+    // Whether games are played as rounds of a longer match rather than on their own
+    pub fn is_match(&self) -> bool {
+        self.best_of > 1 || self.point_target.is_some()
+    }
+    
+    // The player who has won the match, if anyone has yet
+    pub fn winner(&self) -> Option<Uuid> {
+        match self.point_target {
+            Some(target) => self.players.iter().find(|p| p.points >= target),
+            None => self.players.iter().find(|p| p.rounds_won >= self.rounds_to_win()),
+        }
+        .map(|p| p.player_id)
+    }
+    
+    // A player's points so far, if the match is scored
+    pub fn points(&self, player_id: Uuid) -> Option<u32> {
+        self.point_target?;
+        let player = self.players.iter().find(|p| p.player_id == player_id);
+        Some(player.map_or(0, |p| p.points))
+    }
+    
+    // Count a finished round for these players, given in seat order with the cards each had
+    // left. The winner scores every card their opponents were left holding. Draws and
+    // stalemates are played again without either player scoring.
+    pub(super) fn record(&mut self, cards_left: impl IntoIterator<Item = (Uuid, u32)>, outcome: GameOutcome) {
+        let mut points = 0;
+        for (player_id, cards) in cards_left {
+            if !self.players.iter().any(|p| p.player_id == player_id) {
+                self.players.push(SeriesPlayer { player_id, rounds_won: 0, points: 0 });
+            }
+            if outcome != GameOutcome::Win(player_id) {
+                points += cards;
+            }
+        }
+        
+        if let GameOutcome::Win(winner) = outcome {
+            if let Some(player) = self.players.iter_mut().find(|p| p.player_id == winner) {
+                player.rounds_won += 1;
+                player.points += points;
+            }
+        }
+    }
+    
+    // The match as the round after this one starts it: a drawn round is played again under the
+    // same number, and a won one moves the match on to the next
+    pub(super) fn next_round(&self, outcome: GameOutcome) -> Series {
+        let round = match outcome {
+            GameOutcome::Win(_) => self.round + 1,
+            GameOutcome::Draw | GameOutcome::Stalemate => self.round,
+        };
+        Series { round, ..self.clone() }
+    }
+    
+    // Whether the match is between exactly these players
+    pub(super) fn is_between(&self, player_ids: &[Uuid]) -> bool {
+        self.players.len() == player_ids.len() && self.players.iter().all(|p| player_ids.contains(&p.player_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const FIRST: Uuid = Uuid::from_u128(1);
+    const SECOND: Uuid = Uuid::from_u128(2);
+    
+    // Both players with these many cards left, in seat order
+    fn left(first: u32, second: u32) -> [(Uuid, u32); 2] {
+        [(FIRST, first), (SECOND, second)]
+    }
+    
+    #[test]
+    fn match_goes_to_whoever_wins_a_majority_of_rounds() {
+        let mut series = Series::new(3, None);
+        assert_eq!(series.rounds_to_win(), 2);
+        
+        series.record(left(0, 4), GameOutcome::Win(FIRST));
+        assert_eq!(series.winner(), None);
+        series.record(left(2, 0), GameOutcome::Win(SECOND));
+        assert_eq!(series.winner(), None);
+        series.record(left(0, 1), GameOutcome::Win(FIRST));
+        assert_eq!(series.winner(), Some(FIRST));
+        assert_eq!(series.players.iter().map(|p| p.rounds_won).collect::<Vec<_>>(), vec![2, 1]);
+        
+        assert!(series.is_between(&[SECOND, FIRST]));
+        assert!(!series.is_between(&[FIRST, Uuid::from_u128(3)]));
+    }
+    
+    #[test]
+    fn drawn_rounds_are_played_again_without_counting() {
+        let mut series = Series::new(3, Some(10));
+        for outcome in [GameOutcome::Draw, GameOutcome::Stalemate] {
+            series.record(left(2, 3), outcome);
+            series = series.next_round(outcome);
+            assert_eq!(series.round, 1);
+        }
+        assert_eq!(series.players.iter().map(|p| (p.rounds_won, p.points)).collect::<Vec<_>>(), vec![(0, 0), (0, 0)]);
+        assert_eq!(series.points(FIRST), Some(0));
+        assert_eq!(series.winner(), None);
+        
+        series.record(left(0, 3), GameOutcome::Win(FIRST));
+        assert_eq!(series.next_round(GameOutcome::Win(FIRST)).round, 2);
+    }
+    
+    #[test]
+    fn matches_are_at_least_one_round_long() {
+        let series = Series::new(0, None);
+        assert_eq!(series.best_of, 1);
+        assert_eq!(series.rounds_to_win(), 1);
+        assert!(!series.is_match());
+        assert_eq!(Series::new(4, None).rounds_to_win(), 3);
+    }
+    
+    #[test]
+    fn round_winner_scores_the_cards_their_opponent_has_left() {
+        let mut series = Series::new(1, Some(10));
+        assert!(series.is_match());
+        assert_eq!(series.points(FIRST), Some(0));
+        
+        series.record(left(0, 6), GameOutcome::Win(FIRST));
+        series.record(left(3, 0), GameOutcome::Win(SECOND));
+        assert_eq!(series.points(FIRST), Some(6));
+        assert_eq!(series.points(SECOND), Some(3));
+        assert_eq!(series.winner(), None);
+        
+        // Reaching the target ends the match however many rounds were won
+        series.record(left(0, 4), GameOutcome::Win(FIRST));
+        assert_eq!(series.points(FIRST), Some(10));
+        assert_eq!(series.winner(), Some(FIRST));
+    }
+    
+    #[test]
+    fn points_are_only_kept_when_scoring() {
+        let mut series = Series::new(3, None);
+        series.record(left(0, 6), GameOutcome::Win(FIRST));
+        assert_eq!(series.points(FIRST), None);
+        assert_eq!(Series::new(3, Some(0)).point_target, None);
+    }
+}
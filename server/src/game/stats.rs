@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Post-game numbers for every player, in seat order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameStats {
+    pub players: Vec<PlayerStats>,
+}
+
+// How one player played the game
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub player_id: Uuid,
+    pub cards_played: u32,
+    pub flips_requested: u32,
+    // Cards played per flip requested
+    pub efficiency: f64,
+}
+
+impl PlayerStats {
+    pub fn new(player_id: Uuid, cards_played: u32, flips_requested: u32) -> Self {
+        PlayerStats {
+            player_id,
+            cards_played,
+            flips_requested,
+            efficiency: efficiency(cards_played, flips_requested),
+        }
+    }
+}
+
+// Cards played per flip requested. A player who never asked for a flip is scored as if they had
+// asked once, so the metric stays finite and still rewards playing more cards.
+fn efficiency(cards_played: u32, flips_requested: u32) -> f64 {
+    cards_played as f64 / flips_requested.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn efficiency_is_cards_per_flip() {
+        assert_eq!(efficiency(12, 4), 3.0);
+        assert_eq!(efficiency(5, 2), 2.5);
+        assert_eq!(efficiency(0, 3), 0.0);
+    }
+    
+    #[test]
+    fn no_flips_counts_as_one() {
+        assert_eq!(efficiency(7, 0), 7.0);
+        assert_eq!(efficiency(0, 0), 0.0);
+        assert!(PlayerStats::new(Uuid::nil(), 7, 0).efficiency.is_finite());
+    }
+}
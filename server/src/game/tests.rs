@@ -0,0 +1,1343 @@
+use uuid::Uuid;
+
+use super::*;
+
+// Two player ids that stay the same from run to run
+fn player_ids() -> [Uuid; 2] {
+    [Uuid::from_u128(1), Uuid::from_u128(2)]
+}
+
+// A game with both seats filled in connection order, not yet dealt
+fn seated_game(config: GameConfig) -> GameState {
+    let mut game = GameState::new(config);
+    for id in player_ids() {
+        assert!(game.add_player(id));
+    }
+    game
+}
+
+// Each player's seat, in the order the players connected
+fn seats(game: &GameState) -> Vec<usize> {
+    player_ids()
+        .iter()
+        .map(|id| game.players.iter().find(|p| p.id == *id).unwrap().seat)
+        .collect()
+}
+
+// A dealt game from a fixed seed
+fn started_game(config: GameConfig) -> GameState {
+    let mut game = seated_game(GameConfig {
+        seed: Some(config.seed.unwrap_or(1)),
+        ..config
+    });
+    game.start_game().unwrap();
+    assert_eq!(game.phase(), GamePhase::Playing);
+    game
+}
+
+fn act(game: &mut GameState, player_id: Uuid, action: PlayerAction) -> Result<ActionResult, ActionError> {
+    game.process_command(GameCommand {
+        player_id,
+        action,
+        pile_selection: PileSelection::FirstLegal,
+    })
+}
+
+// A card from its name, e.g. "QS" or "10H"
+fn card(name: &str) -> Card {
+    name.parse().unwrap()
+}
+
+// Cards from their names separated by spaces, e.g. "QS 10H"
+fn cards(names: &str) -> Vec<Card> {
+    names.split_whitespace().map(card).collect()
+}
+
+// Replace a dealt game's cards with a board of the test's choosing. Piles are given as their
+// cards from the bottom up, so the last card is the top.
+fn set_board(game: &mut GameState, hands: [&str; 2], draw_piles: [&str; 2], center_piles: &[&str], deck: &str) {
+    for (player, (hand, draw_pile)) in game.players.iter_mut().zip(hands.into_iter().zip(draw_piles)) {
+        player.hand = cards(hand);
+        player.draw_pile = cards(draw_pile).into();
+    }
+    game.center_piles = center_piles.iter().map(|pile| cards(pile)).collect();
+    game.deck = cards(deck);
+}
+
+#[test]
+fn deal_stops_at_the_hand_size_cap() {
+    let game = started_game(GameConfig {
+        hand_size: 7,
+        max_hand_size: 4,
+        ..GameConfig::default()
+    });
+    for player in &game.players {
+        assert_eq!(player.hand.len(), 4);
+        assert_eq!(player.draw_pile.len(), 15);
+    }
+}
+
+#[test]
+fn playing_only_draws_up_to_the_hand_size_cap() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_hand_size: 3,
+        ..GameConfig::default()
+    });
+    
+    // Below the cap a play draws a replacement
+    set_board(&mut game, ["5H 9C 9D", "KC"], ["JC JD", "QC"], &["4S", "2D"], "2C");
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(game.players[0].hand, cards("9C 9D JC"));
+    
+    // A hand already over the cap isn't topped up past it
+    set_board(&mut game, ["5H 9C 9D 9S", "KC"], ["JC JD", "QC"], &["4S", "2D"], "2C");
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(game.players[0].hand, cards("9C 9D 9S"));
+    assert_eq!(game.players[0].draw_pile.len(), 2);
+}
+
+#[test]
+fn unseated_players_get_a_waiting_view() {
+    let stranger = Uuid::from_u128(99);
+    for game in [GameState::new(GameConfig::default()), started_game(GameConfig::default())] {
+        let view = game.create_player_view(stranger);
+        assert_eq!(view.player_id, stranger);
+        assert!(view.hand.is_empty() && view.center_piles.is_empty());
+        assert!(!view.game_started);
+    }
+}
+
+#[test]
+fn any_suit_rule_only_checks_ranks() {
+    assert!(card("5H").can_play_on(&card("4H"), SuitRule::Any, 13));
+    assert!(card("5H").can_play_on(&card("6S"), SuitRule::Any, 13));
+    assert!(!card("5H").can_play_on(&card("7H"), SuitRule::Any, 13));
+    // Ranks wrap around between Ace and the highest rank
+    assert!(card("AH").can_play_on(&card("KS"), SuitRule::Any, 13));
+    assert!(card("7H").can_play_on(&card("AS"), SuitRule::Any, 7));
+}
+
+#[test]
+fn same_suit_rule_needs_a_matching_suit() {
+    assert!(card("5H").can_play_on(&card("4H"), SuitRule::SameSuitRequired, 13));
+    assert!(!card("5H").can_play_on(&card("4S"), SuitRule::SameSuitRequired, 13));
+    assert!(!card("5H").can_play_on(&card("7H"), SuitRule::SameSuitRequired, 13));
+}
+
+#[test]
+fn different_suit_rule_bans_stacking_a_suit() {
+    assert!(card("5H").can_play_on(&card("4S"), SuitRule::DifferentSuitRequired, 13));
+    assert!(!card("5H").can_play_on(&card("4H"), SuitRule::DifferentSuitRequired, 13));
+    assert!(!card("5H").can_play_on(&card("7S"), SuitRule::DifferentSuitRequired, 13));
+}
+
+#[test]
+fn suit_rule_applies_to_plays() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig {
+        suit_rule: SuitRule::SameSuitRequired,
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["5H 5S", "KC"], ["", "QC"], &["4S", "2D"], "2C");
+    assert_eq!(game.validate_move(first, 0, Some(0)), Err(ActionError::CannotPlayOnPile));
+    assert_eq!(game.validate_move(first, 1, None), Ok(0));
+    assert_eq!(act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }), Err(ActionError::NoPlayablePile));
+    assert_eq!(game.players[0].hand, cards("5H 5S"));
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    assert_eq!(game.center_piles[0], cards("4S 5S"));
+}
+
+#[test]
+fn describe_renders_the_board() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["5H 10C", "KD"], ["JC JD", ""], &["3S 4S", ""], "2C 2D 2S");
+    
+    let description = game.describe();
+    assert!(description.contains(&format!("Player 1 ({}): hand [5H 10C], draw pile 2", first)));
+    assert!(description.contains(&format!("Player 2 ({}): hand [KD], draw pile 0", second)));
+    assert!(description.contains("Center piles: 4S (2 cards), empty"));
+    assert!(description.contains("Deck: 3 cards remaining"));
+    assert!(description.contains("Winner: none"));
+    
+    game.forfeit(second);
+    assert!(game.describe().contains(&format!("Winner: {}", first)));
+}
+
+#[test]
+fn seat_requests_fill_the_requested_seat() {
+    let [first, second] = player_ids();
+    let mut game = GameState::new(GameConfig {
+        allow_seat_requests: true,
+        ..GameConfig::default()
+    });
+    game.add_player(first);
+    assert!(game.request_seat(first, 1));
+    assert_eq!(game.players[0].seat, 1);
+    
+    // The next player takes the seat that was left free
+    game.add_player(second);
+    assert_eq!(seats(&game), vec![1, 0]);
+    
+    // Taken and nonexistent seats are refused
+    assert!(!game.request_seat(second, 1));
+    assert!(!game.request_seat(second, MAX_PLAYERS));
+    assert_eq!(seats(&game), vec![1, 0]);
+}
+
+#[test]
+fn seat_requests_are_refused_unless_enabled() {
+    let [first, _] = player_ids();
+    let mut game = GameState::new(GameConfig::default());
+    game.add_player(first);
+    assert!(!game.request_seat(first, 1));
+    assert_eq!(game.players[0].seat, 0);
+}
+
+// Tops of the center piles after one flip of a known deck
+fn tops_after_flip(config: GameConfig) -> Vec<Card> {
+    let [first, _] = player_ids();
+    let mut game = started_game(config);
+    set_board(&mut game, ["KH", "KD"], ["", ""], &["9S", "9C"], "6S 2C 3C");
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    game.center_piles.iter().map(|pile| *pile.last().unwrap()).collect()
+}
+
+#[test]
+fn flips_go_left_to_right_by_default() {
+    assert_eq!(tops_after_flip(GameConfig::default()), cards("3C 2C"));
+}
+
+#[test]
+fn randomized_flips_are_reproducible_for_a_seed() {
+    let config = |seed| GameConfig {
+        randomize_flip: true,
+        seed: Some(seed),
+        ..GameConfig::default()
+    };
+    for seed in 0..16 {
+        assert_eq!(tops_after_flip(config(seed)), tops_after_flip(config(seed)));
+    }
+    assert!((0..16).any(|seed| tops_after_flip(config(seed)) == cards("2C 3C")));
+}
+
+#[test]
+fn rejected_actions_change_nothing() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["KH", "10D"], ["", ""], &["9S", "9C"], "");
+    let before = game.describe();
+    
+    assert_eq!(act(&mut game, first, PlayerAction::PlayCard { card_index: 5, pile_index: None }), Err(ActionError::InvalidCardIndex));
+    assert_eq!(act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }), Err(ActionError::NoPlayablePile));
+    assert_eq!(act(&mut game, first, PlayerAction::RequestNewCenterCards), Err(ActionError::FlipRejected(FlipRejection::DeckEmpty)));
+    assert_eq!(game.describe(), before);
+}
+
+#[test]
+fn cards_parse_from_their_names() {
+    assert_eq!(card("QS"), Card { suit: Suit::Spades, rank: Rank::Queen });
+    assert_eq!(card("10H"), Card { suit: Suit::Hearts, rank: Rank::Ten });
+    assert_eq!(card("ac"), Card { suit: Suit::Clubs, rank: Rank::Ace });
+    assert_eq!(card(" 7D "), Card { suit: Suit::Diamonds, rank: Rank::Seven });
+}
+
+#[test]
+fn every_card_round_trips_through_display() {
+    for card in create_deck(13) {
+        assert_eq!(card.to_string().parse::<Card>(), Ok(card));
+    }
+}
+
+#[test]
+fn invalid_card_names_are_rejected() {
+    for name in ["1Z", "1H", "11H", "Q", "S", "", "QX", "10"] {
+        let error = name.parse::<Card>().unwrap_err();
+        assert!(error.to_string().contains(&format!("{:?}", name)));
+    }
+}
+
+// A full deck with these cards moved to the front, in order
+fn deck_starting_with(first: &str) -> Vec<Card> {
+    let first = cards(first);
+    let rest = create_deck(13).into_iter().filter(|card| !first.contains(card));
+    first.iter().copied().chain(rest).collect()
+}
+
+#[test]
+fn injected_deck_is_dealt_in_order() {
+    let deck = deck_starting_with("KS QS JS 10S 9S");
+    let mut game = seated_game(GameConfig::default());
+    game.start_game_with_deck(deck.clone()).unwrap();
+    
+    // Each player gets their hand then their draw pile, then the center piles get a card each
+    assert_eq!(game.players[0].hand, cards("KS QS JS 10S 9S"));
+    assert_eq!(game.players[0].draw_pile, deck[5..20].to_vec());
+    assert_eq!(game.players[1].hand, deck[20..25]);
+    assert_eq!(game.players[1].draw_pile, deck[25..40].to_vec());
+    assert_eq!(game.center_piles, vec![vec![deck[40]], vec![deck[41]]]);
+    assert_eq!(game.deck.len(), 10);
+}
+
+#[test]
+fn injected_deck_must_be_complete() {
+    let mut short = create_deck(13);
+    short.pop();
+    let mut doubled = short.clone();
+    doubled.push(doubled[0]);
+    for deck in [short, doubled] {
+        let mut game = seated_game(GameConfig::default());
+        assert_eq!(game.start_game_with_deck(deck), Err(StartError::InvalidDeck));
+        assert!(!game.phase().has_started());
+    }
+}
+
+#[test]
+fn emptying_hand_and_draw_pile_wins() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["5H", "KC QC"], ["", "JC"], &["4S", "9D"], "2C");
+    assert!(!game.is_game_over());
+    assert_eq!(game.outcome(), None);
+    
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert!(game.is_game_over());
+    assert_eq!(game.outcome(), Some(GameOutcome::Win(first)));
+}
+
+#[test]
+fn stuck_board_with_an_empty_deck_is_a_stalemate() {
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "");
+    game.settle_outcome();
+    assert!(game.is_game_over());
+    assert_eq!(game.outcome(), Some(GameOutcome::Stalemate));
+}
+
+#[test]
+fn stuck_board_is_no_stalemate_while_a_redeal_is_left() {
+    let mut game = started_game(GameConfig {
+        max_redeals: 1,
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "");
+    game.settle_outcome();
+    assert_eq!(game.outcome(), None);
+    assert_eq!(game.progress_possible(), !game.is_game_over());
+}
+
+#[test]
+fn drawn_round_of_a_match_is_played_again() {
+    let config = GameConfig {
+        max_consecutive_flips: Some(3),
+        best_of: 3,
+        ..GameConfig::default()
+    };
+    let mut game = started_game(config.clone());
+    game.consecutive_flips = 3;
+    game.settle_outcome();
+    assert_eq!(game.outcome(), Some(GameOutcome::Draw));
+    
+    let mut replay = seated_game(config);
+    assert!(replay.continue_series(&game));
+    assert_eq!(replay.series().round, 1);
+}
+
+#[test]
+fn stuck_board_is_no_stalemate_while_a_pile_can_be_cleared() {
+    let mut game = started_game(GameConfig {
+        clear_pile_cooldown: Some(0),
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "");
+    game.settle_outcome();
+    assert_eq!(game.outcome(), None);
+    assert_eq!(game.progress_possible(), !game.is_game_over());
+}
+
+#[test]
+fn too_many_flips_in_a_row_is_a_draw() {
+    let mut game = started_game(GameConfig {
+        max_consecutive_flips: Some(3),
+        ..GameConfig::default()
+    });
+    game.consecutive_flips = 3;
+    game.settle_outcome();
+    assert_eq!(game.outcome(), Some(GameOutcome::Draw));
+}
+
+#[test]
+fn pile_hints_mark_only_piles_the_player_can_play_on() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        pile_hints: true,
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["AH 9C", "6D"], ["", ""], &["KS", "5C", "3D"], "");
+    // Aces wrap onto Kings, and nothing in the first hand fits a 3
+    assert_eq!(game.create_player_view(first).playable_piles, vec![true, false, false]);
+    assert_eq!(game.create_player_view(second).playable_piles, vec![false, true, false]);
+    
+    // The suit rule is part of what counts as playable
+    game.config.suit_rule = SuitRule::DifferentSuitRequired;
+    game.center_piles[0] = cards("KH");
+    assert_eq!(game.create_player_view(first).playable_piles, vec![false, false, false]);
+}
+
+#[test]
+fn pile_hints_are_left_out_unless_enabled() {
+    let [first, _] = player_ids();
+    let game = started_game(GameConfig::default());
+    assert!(game.create_player_view(first).playable_piles.is_empty());
+}
+
+#[test]
+fn later_play_to_the_same_pile_is_checked_against_the_new_top() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["5H KC", "3D 6D"], ["", ""], &["4S", "9C"], "2C");
+    
+    // Both players aim for the 4 at once; the first play processed takes the pile
+    let play = |card_index| PlayerAction::PlayCard { card_index, pile_index: None };
+    act(&mut game, first, play(0)).unwrap();
+    assert_eq!(act(&mut game, second, play(0)), Err(ActionError::NoPlayablePile));
+    assert_eq!(game.players[1].hand, cards("3D 6D"));
+    
+    // A later play that still fits the new top goes through
+    act(&mut game, second, play(1)).unwrap();
+    assert_eq!(game.center_piles[0], cards("4S 5H 6D"));
+}
+
+#[test]
+fn deck_is_only_built_when_the_game_starts() {
+    let game = seated_game(GameConfig::default());
+    assert!(game.deck.is_empty());
+    assert_eq!(game.deck.capacity(), 0);
+    
+    let game = started_game(GameConfig::default());
+    let dealt: usize = game.players.iter().map(|p| p.hand.len() + p.draw_pile.len()).sum();
+    let center: usize = game.center_piles.iter().map(|pile| pile.len()).sum();
+    assert_eq!(game.deck.len() + dealt + center, 52);
+}
+
+#[test]
+fn redeal_needs_both_players_to_ask() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_redeals: 1,
+        ..GameConfig::default()
+    });
+    let seats = |game: &GameState| game.players.iter().map(|p| (p.id, p.seat)).collect::<Vec<_>>();
+    let before_seats = seats(&game);
+    game.players[0].hand.pop();
+    let before = game.players[0].hand.clone();
+    
+    // One request is only recorded, and can't be repeated
+    act(&mut game, first, PlayerAction::RequestRedeal).unwrap();
+    assert_eq!(game.players[0].hand, before);
+    assert_eq!(game.redeals, 0);
+    assert!(matches!(
+        act(&mut game, first, PlayerAction::RequestRedeal),
+        Err(ActionError::RedealAlreadyRequested)
+    ));
+    
+    act(&mut game, second, PlayerAction::RequestRedeal).unwrap();
+    assert_eq!(game.redeals, 1);
+    assert!(game.redeal_requests.is_empty());
+    assert_eq!(game.players[0].hand.len(), before.len() + 1);
+    assert_eq!(seats(&game), before_seats);
+    
+    // Redeals beyond the limit are refused
+    assert!(matches!(
+        act(&mut game, first, PlayerAction::RequestRedeal),
+        Err(ActionError::RedealNotAllowed)
+    ));
+}
+
+#[test]
+fn outcomes_serialize_distinctly() {
+    let [first, _] = player_ids();
+    let outcomes = [GameOutcome::Win(first), GameOutcome::Draw, GameOutcome::Stalemate];
+    let encoded: Vec<String> = outcomes.iter().map(|o| serde_json::to_string(o).unwrap()).collect();
+    for (i, a) in encoded.iter().enumerate() {
+        assert!(encoded[i + 1..].iter().all(|b| a != b));
+    }
+    for (outcome, json) in outcomes.iter().zip(&encoded) {
+        assert_eq!(&serde_json::from_str::<GameOutcome>(json).unwrap(), outcome);
+    }
+}
+
+#[test]
+fn views_carry_the_outcome_once_over() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    assert_eq!(game.create_player_view(first).outcome, None);
+    
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "");
+    game.settle_outcome();
+    for id in [first, second] {
+        let view = game.create_player_view(id);
+        assert_eq!(view.outcome, Some(GameOutcome::Stalemate));
+        assert_eq!(view.winner, None);
+    }
+}
+
+#[test]
+fn play_reports_the_pile_it_landed_on() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["5H 8C", "KD"], ["", "9D"], &["JC", "4S", "6D"], "2C");
+    
+    // The card fits both the second and third piles; the server picks the first that fits
+    let result = act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(result.pile_index, Some(1));
+    assert_eq!(game.center_piles[1].last(), Some(&card("5H")));
+    assert_eq!(serde_json::to_value(result).unwrap(), serde_json::json!({ "pile_index": 1 }));
+}
+
+#[test]
+fn add_player_fills_two_seats_with_distinct_players() {
+    let [first, second] = player_ids();
+    let mut game = GameState::new(GameConfig::default());
+    assert!(game.add_player(first));
+    
+    // A repeated id is refused without taking the other seat
+    assert!(!game.add_player(first));
+    assert_eq!(game.players.len(), 1);
+    
+    assert!(game.add_player(second));
+    assert!(!game.add_player(Uuid::from_u128(3)));
+    assert!(!game.add_player(second));
+    assert_eq!(game.players.len(), 2);
+}
+
+// Play a game out greedily like `--simulate`, calling `inspect` on the state before the first
+// action and after every one
+fn play_out(game: &mut GameState, mut inspect: impl FnMut(&GameState)) {
+    inspect(game);
+    while !game.is_game_over() {
+        let mut anyone_played = false;
+        for id in player_ids() {
+            if let Some(card_index) = game.find_playable_card(id) {
+                if act(game, id, PlayerAction::PlayCard { card_index, pile_index: None }).is_ok() {
+                    anyone_played = true;
+                    inspect(game);
+                }
+            }
+            if game.is_game_over() {
+                return;
+            }
+        }
+        if !anyone_played {
+            if act(game, player_ids()[0], PlayerAction::RequestNewCenterCards).is_err() {
+                return;
+            }
+            inspect(game);
+        }
+    }
+}
+
+#[test]
+fn player_views_never_carry_hidden_cards() {
+    for seed in 0..8 {
+        let mut game = started_game(GameConfig {
+            seed: Some(seed),
+            pile_hints: true,
+            ..GameConfig::default()
+        });
+        let mut views = 0;
+        play_out(&mut game, |game| {
+            for player in &game.players {
+                let json = serde_json::to_string(&game.create_player_view(player.id)).unwrap();
+                let hidden = game
+                    .players
+                    .iter()
+                    .filter(|p| p.id != player.id)
+                    .flat_map(|p| p.hand.iter().chain(p.draw_pile.iter()))
+                    .chain(player.draw_pile.iter())
+                    .chain(game.deck.iter());
+                for card in hidden {
+                    let card_json = serde_json::to_string(card).unwrap();
+                    assert!(!json.contains(&card_json), "view for seat {} shows hidden card {}", player.seat, card);
+                }
+                // The player's own hand is there in the same form, so the check above can see cards
+                for card in &player.hand {
+                    assert!(json.contains(&serde_json::to_string(card).unwrap()));
+                }
+                views += 1;
+            }
+        });
+        assert!(views > 2);
+    }
+}
+
+// Flip the last cards from the deck of a board where the first player then has a play
+fn flip_last_cards(config: GameConfig) -> GameState {
+    let [first, _] = player_ids();
+    let mut game = started_game(config);
+    set_board(&mut game, ["KH 3C 4D", "KD QC"], ["9H", "9D"], &["5S", "5C"], "2S 2H");
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    game
+}
+
+#[test]
+fn sudden_death_starts_when_the_deck_runs_out() {
+    let [first, _] = player_ids();
+    let config = GameConfig {
+        sudden_death: true,
+        ..GameConfig::default()
+    };
+    let mut game = started_game(config.clone());
+    set_board(&mut game, ["KH 3C 4D", "KD QC"], ["9H", "9D"], &["5S", "5C"], "2S 2H");
+    game.update_sudden_death();
+    assert!(!game.sudden_death);
+    
+    let mut game = flip_last_cards(config);
+    assert!(game.sudden_death);
+    assert!(game.create_player_view(first).sudden_death);
+    
+    // The next play wins, however many cards the player has left
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    assert_eq!(game.outcome(), Some(GameOutcome::Win(first)));
+    assert_eq!(game.players[0].hand.len() + game.players[0].draw_pile.len(), 3);
+}
+
+#[test]
+fn sudden_death_is_off_unless_enabled() {
+    let [first, _] = player_ids();
+    let mut game = flip_last_cards(GameConfig::default());
+    assert!(!game.sudden_death);
+    
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn observer_views_carry_only_counts_and_tops() {
+    let mut game = started_game(GameConfig::default());
+    play_out(&mut game, |game| {
+        let view = game.create_observer_view();
+        let json = serde_json::to_string(&view).unwrap();
+        for player in &game.players {
+            assert!(!json.contains(&player.id.to_string()));
+            assert!(!json.contains(&player.id.simple().to_string()));
+            for card in player.hand.iter().chain(player.draw_pile.iter()) {
+                assert!(!json.contains(&serde_json::to_string(card).unwrap()), "observer view shows {}", card);
+            }
+            
+            let seat = view.seats.iter().find(|s| s.seat == player.seat).unwrap();
+            assert_eq!(seat.hand_count, player.hand.len());
+            assert_eq!(seat.draw_pile_count, player.draw_pile.len());
+        }
+        let tops: Vec<Option<Card>> = game.center_piles.iter().map(|pile| pile.last().copied()).collect();
+        assert_eq!(view.center_tops, tops);
+    });
+}
+
+#[test]
+fn stats_score_cards_played_per_flip() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["KH 7C 8D", "KD"], ["9H", "9D"], &["5S", "5C"], "2S 2H 4S 4H 6S 6H");
+    
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    
+    let stats = game.stats();
+    let of = |id| stats.players.iter().find(|p| p.player_id == id).unwrap();
+    assert_eq!((of(first).cards_played, of(first).flips_requested), (2, 1));
+    assert_eq!(of(first).efficiency, 2.0);
+    assert_eq!((of(second).cards_played, of(second).flips_requested), (0, 0));
+    assert_eq!(of(second).efficiency, 0.0);
+}
+
+// Every card in the game, wherever it is
+fn card_count(game: &GameState) -> usize {
+    let held: usize = game.players.iter().map(|p| p.hand.len() + p.draw_pile.len()).sum();
+    let center: usize = game.center_piles.iter().map(|pile| pile.len()).sum();
+    held + center + game.deck.len()
+}
+
+#[test]
+fn clearing_a_pile_returns_its_cards_to_the_deck() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig {
+        clear_pile_cooldown: Some(1),
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["5H KH", "KD"], ["9H", "9D"], &["3S 4S", "JC"], "2C");
+    let clear = |pile_index| PlayerAction::ClearPile { pile_index };
+    let total = card_count(&game);
+    
+    // The cooldown runs from the player's last clear, so the first needs no cards played
+    act(&mut game, first, clear(0)).unwrap();
+    assert!(game.center_piles[0].is_empty());
+    assert_eq!(game.deck, cards("3S 4S 2C"));
+    assert_eq!(card_count(&game), total);
+    
+    assert!(matches!(act(&mut game, first, clear(0)), Err(ActionError::PileAlreadyEmpty)));
+    assert!(matches!(act(&mut game, first, clear(1)), Err(ActionError::ClearPileOnCooldown)));
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    act(&mut game, first, clear(1)).unwrap();
+    assert_eq!(game.deck, cards("JC 3S 4S 2C"));
+    
+    assert!(matches!(act(&mut game, first, clear(2)), Err(ActionError::InvalidPileIndex)));
+    assert_eq!(card_count(&game), total);
+}
+
+#[test]
+fn clearing_a_pile_ends_sudden_death() {
+    let [first, _] = player_ids();
+    let mut game = flip_last_cards(GameConfig {
+        sudden_death: true,
+        clear_pile_cooldown: Some(0),
+        ..GameConfig::default()
+    });
+    assert!(game.sudden_death);
+    
+    // With cards to flip again, the next play no longer wins
+    act(&mut game, first, PlayerAction::ClearPile { pile_index: 0 }).unwrap();
+    assert!(!game.sudden_death);
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn clearing_a_pile_is_refused_unless_enabled() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    let piles = game.center_piles.clone();
+    assert!(matches!(
+        act(&mut game, first, PlayerAction::ClearPile { pile_index: 0 }),
+        Err(ActionError::ClearPileNotAllowed)
+    ));
+    assert_eq!(game.center_piles, piles);
+}
+
+#[test]
+fn remaining_cards_are_only_revealed_once_the_game_is_over() {
+    let mut game = started_game(GameConfig {
+        reveal_on_game_over: true,
+        ..GameConfig::default()
+    });
+    play_out(&mut game, |game| {
+        if !game.is_game_over() {
+            assert!(game.revealed_cards().is_none());
+        }
+    });
+    assert!(game.is_game_over());
+    
+    let revealed = game.revealed_cards().unwrap();
+    for player in &game.players {
+        let cards = revealed.iter().find(|r| r.player_id == player.id).unwrap();
+        assert_eq!(cards.hand, player.hand);
+        assert_eq!(cards.draw_pile, player.draw_pile.iter().copied().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn start_needs_two_players() {
+    let mut game = GameState::new(GameConfig::default());
+    assert_eq!(game.start_game(), Err(StartError::WrongPlayerCount));
+    game.add_player(player_ids()[0]);
+    assert_eq!(game.start_game(), Err(StartError::WrongPlayerCount));
+    assert!(game.deck.is_empty());
+}
+
+#[test]
+fn start_is_refused_once_started() {
+    let mut game = started_game(GameConfig::default());
+    let hands: Vec<Vec<Card>> = game.players.iter().map(|p| p.hand.clone()).collect();
+    assert_eq!(game.start_game(), Err(StartError::AlreadyStarted));
+    assert_eq!(game.players.iter().map(|p| p.hand.clone()).collect::<Vec<_>>(), hands);
+}
+
+#[test]
+fn start_needs_enough_cards_for_the_deal() {
+    let mut game = seated_game(GameConfig {
+        draw_pile_size: 30,
+        ..GameConfig::default()
+    });
+    assert_eq!(game.start_game(), Err(StartError::InsufficientDeck));
+    assert!(!game.phase().has_started());
+}
+
+#[test]
+fn flipping_without_playing_ends_in_a_draw() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_consecutive_flips: Some(3),
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "2S 2H 3S 3H 4S 4H 6S 6H");
+    
+    // The players take turns flipping instead of playing
+    for (flip, player) in [first, second, first].into_iter().enumerate() {
+        assert!(!game.is_game_over(), "game ended after {} flips", flip);
+        act(&mut game, player, PlayerAction::RequestNewCenterCards).unwrap();
+    }
+    assert_eq!(game.outcome(), Some(GameOutcome::Draw));
+}
+
+#[test]
+fn playing_resets_the_flip_count() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_consecutive_flips: Some(2),
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["KH 7C", "KD"], ["9H", "9D"], &["5S", "5C"], "2S 2H 4S 4H 6S 6H");
+    
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 1, pile_index: None }).unwrap();
+    act(&mut game, second, PlayerAction::RequestNewCenterCards).unwrap();
+    assert!(!game.is_game_over());
+    assert_eq!(game.consecutive_flips, 1);
+}
+
+#[test]
+fn daily_seed_stays_hidden_until_game_over() {
+    let [first, _] = player_ids();
+    let secret = DailySecret::new("test-secret".to_string());
+    let mut game = started_game(GameConfig {
+        mode: GameMode::DailyChallenge,
+        daily_secret: secret.clone(),
+        ..GameConfig::default()
+    });
+    let date = game.challenge_date.clone().expect("daily games carry their date");
+    let seed = daily_seed(&secret, &date);
+    
+    // Nothing sent during the game carries the seed or the secret
+    let sent = [
+        serde_json::to_string(&game.config.public()).unwrap(),
+        serde_json::to_string(&game.create_player_view(first)).unwrap(),
+    ];
+    for json in sent {
+        assert!(!json.contains(&seed.to_string()));
+        assert!(!json.contains("test-secret"));
+    }
+    assert!(game.deal_reveal().is_none());
+    
+    assert!(game.forfeit(first));
+    let reveal = game.deal_reveal().expect("deal is revealed at game over");
+    assert_eq!(reveal.seed, seed);
+    assert!(reveal.matches(&game.provenance().unwrap().deck_commitment));
+}
+
+#[test]
+fn revealed_deal_matches_the_commitment_from_the_start() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        seed: Some(7),
+        ..GameConfig::default()
+    });
+    let provenance = game.create_player_view(first).game_provenance.unwrap();
+    assert_eq!(provenance.seats, vec![first, second]);
+    assert_eq!(provenance.center_piles, 2);
+    assert!(game.deal_reveal().is_none());
+    
+    play_out(&mut game, |_| {});
+    let reveal = game.deal_reveal().unwrap();
+    assert!(reveal.matches(&provenance.deck_commitment));
+    assert!(reveal.matches(&provenance.deck_commitment.to_uppercase()));
+    
+    // The revealed seed deals the revealed deck again
+    let replay = started_game(GameConfig {
+        seed: Some(reveal.seed),
+        ..GameConfig::default()
+    });
+    assert_eq!(replay.dealt_deck, reveal.deck);
+    
+    // Any change to the seed or the deck breaks the commitment
+    let wrong_seed = DealReveal { seed: reveal.seed + 1, ..reveal.clone() };
+    assert!(!wrong_seed.matches(&provenance.deck_commitment));
+    let mut swapped = reveal.clone();
+    swapped.deck.swap(0, 1);
+    assert!(!swapped.matches(&provenance.deck_commitment));
+}
+
+#[test]
+fn actions_from_unseated_players_are_rejected_without_changes() {
+    let mut game = started_game(GameConfig {
+        max_redeals: 1,
+        clear_pile_cooldown: Some(0),
+        ..GameConfig::default()
+    });
+    let stranger = Uuid::from_u128(3);
+    let deck = game.deck.clone();
+    let piles = game.center_piles.clone();
+    
+    let actions = [
+        PlayerAction::PlayCard { card_index: 0, pile_index: None },
+        PlayerAction::RequestNewCenterCards,
+        PlayerAction::RequestRedeal,
+        PlayerAction::ClearPile { pile_index: 0 },
+    ];
+    for action in actions {
+        assert!(matches!(act(&mut game, stranger, action), Err(ActionError::PlayerNotFound)));
+    }
+    assert_eq!(game.deck, deck);
+    assert_eq!(game.center_piles, piles);
+    assert!(game.redeal_requests.is_empty());
+    assert_eq!(game.consecutive_flips, 0);
+}
+
+#[test]
+fn fingerprint_is_stable_until_a_hidden_card_moves() {
+    let game = started_game(GameConfig::default());
+    assert_eq!(game.deck_fingerprint(), started_game(GameConfig::default()).deck_fingerprint());
+    assert_ne!(
+        game.deck_fingerprint(),
+        started_game(GameConfig {
+            seed: Some(2),
+            ..GameConfig::default()
+        })
+        .deck_fingerprint()
+    );
+    
+    let moves: [fn(&mut GameState); 4] = [
+        |game| game.deck.swap(0, 1),
+        |game| game.players[0].hand.swap(0, 1),
+        |game| game.players[1].draw_pile.swap(0, 1),
+        // A card moving from one draw pile to the other
+        |game| {
+            let card = game.players[0].draw_pile.pop_back().unwrap();
+            game.players[1].draw_pile.push_front(card);
+        },
+    ];
+    for change in moves {
+        let mut moved = game.clone();
+        change(&mut moved);
+        assert_ne!(moved.deck_fingerprint(), game.deck_fingerprint());
+    }
+    
+    // Center piles are public, so they aren't part of it
+    let mut moved = game.clone();
+    moved.center_piles.swap(0, 1);
+    assert_eq!(moved.deck_fingerprint(), game.deck_fingerprint());
+}
+
+#[test]
+fn blitz_preset_deals_a_smaller_deck() {
+    let config = Preset::Blitz.config();
+    assert_eq!(config.deck_size(), 28);
+    
+    let game = started_game(config);
+    assert_eq!(card_count(&game), 28);
+    assert!(game.dealt_deck.iter().all(|card| card.rank as u8 <= 7));
+    for player in &game.players {
+        assert_eq!((player.hand.len(), player.draw_pile.len()), (3, 9));
+    }
+    assert_eq!(game.center_piles.len(), 1);
+    assert_eq!(game.deck.len(), 3);
+}
+
+// Ask for a flip on a board where nobody has a move, once the test has changed it
+fn flip_rejection(config: GameConfig, change: impl FnOnce(&mut GameState)) -> Option<FlipRejection> {
+    let [first, _] = player_ids();
+    let mut game = started_game(config);
+    set_board(&mut game, ["KH", "KD"], ["9H", "9D"], &["5S", "5C"], "2S 2H");
+    change(&mut game);
+    match act(&mut game, first, PlayerAction::RequestNewCenterCards) {
+        Ok(_) => None,
+        Err(ActionError::FlipRejected(reason)) => Some(reason),
+        Err(e) => panic!("flip failed for another reason: {}", e),
+    }
+}
+
+#[test]
+fn each_flip_rejection_has_its_own_reason() {
+    let when_stuck = GameConfig {
+        flip_only_when_stuck: true,
+        ..GameConfig::default()
+    };
+    assert_eq!(flip_rejection(when_stuck.clone(), |_| {}), None);
+    // The opponent keeps a move, so the empty deck isn't a stalemate
+    let empty_deck = |game: &mut GameState| {
+        game.deck.clear();
+        game.players[1].hand = cards("6D");
+    };
+    assert_eq!(flip_rejection(GameConfig::default(), empty_deck), Some(FlipRejection::DeckEmpty));
+    assert_eq!(
+        flip_rejection(when_stuck.clone(), |game| game.players[0].hand = cards("4H")),
+        Some(FlipRejection::PlayerCanPlay)
+    );
+    assert_eq!(
+        flip_rejection(when_stuck, |game| game.players[1].hand = cards("6D")),
+        Some(FlipRejection::OpponentCanPlay)
+    );
+    assert_eq!(
+        flip_rejection(GameConfig::default(), |game| game.players[0].flip_blocked = true),
+        Some(FlipRejection::Blocked)
+    );
+    
+    // Without the stuck rule a player with a move may still flip
+    assert_eq!(flip_rejection(GameConfig::default(), |game| game.players[0].hand = cards("4H")), None);
+    
+    let reasons = [
+        FlipRejection::DeckEmpty,
+        FlipRejection::PlayerCanPlay,
+        FlipRejection::OpponentCanPlay,
+        FlipRejection::Blocked,
+    ];
+    let codes: std::collections::HashSet<_> = reasons.iter().map(|r| ActionError::FlipRejected(*r).code()).collect();
+    assert_eq!(codes.len(), reasons.len());
+}
+
+#[test]
+fn validate_move_agrees_with_playing_the_card() {
+    let [first, second] = player_ids();
+    let (mut accepted, mut rejected) = (0, 0);
+    for seed in 0..6 {
+        let game = started_game(GameConfig {
+            seed: Some(seed),
+            suit_rule: if seed % 2 == 0 { SuitRule::Any } else { SuitRule::DifferentSuitRequired },
+            ..GameConfig::default()
+        });
+        for player_id in [first, second, Uuid::from_u128(3)] {
+            for card_index in 0..7 {
+                for pile_index in [None, Some(0), Some(1), Some(2)] {
+                    let validated = game.validate_move(player_id, card_index, pile_index);
+                    let mut played = game.clone();
+                    let result = act(&mut played, player_id, PlayerAction::PlayCard { card_index, pile_index });
+                    assert_eq!(result.map(|r| r.pile_index.unwrap()), validated);
+                    if validated.is_ok() {
+                        accepted += 1;
+                    } else {
+                        rejected += 1;
+                    }
+                }
+            }
+        }
+    }
+    assert!(accepted > 0 && rejected > 0);
+}
+
+#[test]
+fn phases_follow_the_game_lifecycle() {
+    let [first, second] = player_ids();
+    let mut game = GameState::new(GameConfig::default());
+    assert_eq!(game.phase(), GamePhase::Waiting);
+    
+    // Playing isn't possible before the seats are filled and the cards are dealt
+    let play = PlayerAction::PlayCard { card_index: 0, pile_index: None };
+    game.add_player(first);
+    assert_eq!(
+        act(&mut game, first, play.clone()).unwrap_err(),
+        ActionError::ActionNotAllowedInPhase { phase: GamePhase::Waiting, action: ActionKind::PlayCard }
+    );
+    assert_eq!(
+        game.transition_to(GamePhase::Playing),
+        Err(InvalidTransition { from: GamePhase::Waiting, to: GamePhase::Playing })
+    );
+    
+    game.add_player(second);
+    assert_eq!(game.phase(), GamePhase::Ready);
+    assert!(act(&mut game, first, play.clone()).is_err());
+    game.start_game().unwrap();
+    assert_eq!(game.phase(), GamePhase::Playing);
+    
+    // A paused game takes no actions until it is resumed
+    game.transition_to(GamePhase::Paused).unwrap();
+    assert!(matches!(
+        act(&mut game, first, PlayerAction::RequestNewCenterCards),
+        Err(ActionError::ActionNotAllowedInPhase { phase: GamePhase::Paused, .. })
+    ));
+    assert!(game.transition_to(GamePhase::Ready).is_err());
+    game.transition_to(GamePhase::Playing).unwrap();
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    
+    // Nothing leaves the end of a game
+    assert!(game.forfeit(first));
+    assert_eq!(game.phase(), GamePhase::Over);
+    for phase in [GamePhase::Waiting, GamePhase::Ready, GamePhase::Playing, GamePhase::Paused, GamePhase::Over] {
+        assert!(game.transition_to(phase).is_err());
+    }
+    assert!(act(&mut game, second, play).is_err());
+}
+
+#[test]
+fn randomized_seats_are_reproducible_for_a_seed() {
+    let config = |seed| GameConfig {
+        randomize_seats: true,
+        seed: Some(seed),
+        ..GameConfig::default()
+    };
+    for seed in 0..16 {
+        assert_eq!(seats(&seated_game(config(seed))), seats(&seated_game(config(seed))));
+    }
+    
+    // Some seeds seat the first player to connect in the second seat
+    assert!((0..16).any(|seed| seats(&seated_game(config(seed))) == vec![1, 0]));
+    assert_eq!(seats(&seated_game(GameConfig::default())), vec![0, 1]);
+}
+
+fn powered_game() -> GameState {
+    started_game(GameConfig {
+        powers: "J=skip_flip,2=reset_pile".parse().unwrap(),
+        ..GameConfig::default()
+    })
+}
+
+#[test]
+fn skip_power_blocks_the_opponents_next_flip() {
+    let [first, second] = player_ids();
+    let mut game = powered_game();
+    set_board(&mut game, ["JH 5H", "KD"], ["9H", "9D"], &["10S", "4C"], "2S 3H 4S 6H");
+    
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(
+        act(&mut game, second, PlayerAction::RequestNewCenterCards).unwrap_err(),
+        ActionError::FlipRejected(FlipRejection::Blocked)
+    );
+    
+    // The player's own flip lifts the block
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    act(&mut game, second, PlayerAction::RequestNewCenterCards).unwrap();
+}
+
+#[test]
+fn reset_power_sends_the_pile_under_the_deck() {
+    let [first, _] = player_ids();
+    let mut game = powered_game();
+    set_board(&mut game, ["2H", "KD"], ["9H", "9D"], &["QS KS AS", "7C"], "5C");
+    let total = card_count(&game);
+    
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(game.center_piles[0], cards("2H"));
+    assert_eq!(game.deck, cards("QS KS AS 5C"));
+    assert_eq!(card_count(&game), total);
+}
+
+#[test]
+fn reset_power_ends_sudden_death_with_the_play_that_triggered_it() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig {
+        powers: "2=reset_pile".parse().unwrap(),
+        sudden_death: true,
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["2H 9C", "KD"], ["9H", "9D"], &["QS KS AS", "7C"], "");
+    game.update_sudden_death();
+    assert!(game.sudden_death);
+    
+    // The deck is refilled, but the play that did it still wins
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(game.deck, cards("QS KS AS"));
+    assert_eq!(game.outcome(), Some(GameOutcome::Win(first)));
+}
+
+#[test]
+fn ranks_without_powers_play_normally() {
+    let [first, second] = player_ids();
+    let mut game = powered_game();
+    set_board(&mut game, ["QH", "KD"], ["9H", "9D"], &["JS KS", "7C"], "5C 6C");
+    
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    assert_eq!(game.center_piles[0], cards("JS KS QH"));
+    assert!(game.players.iter().all(|p| !p.flip_blocked));
+    act(&mut game, second, PlayerAction::RequestNewCenterCards).unwrap();
+    
+    // Without powers configured, a Jack is just a Jack
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["JH", "KD"], ["9H", "9D"], &["10S", "4C"], "2S 3H");
+    act(&mut game, first, PlayerAction::PlayCard { card_index: 0, pile_index: None }).unwrap();
+    act(&mut game, second, PlayerAction::RequestNewCenterCards).unwrap();
+}
+
+// A board nobody can play on, with this left in the deck
+fn blocked_board(detect_dead_games: bool, deck: &str) -> GameState {
+    let mut game = started_game(GameConfig {
+        detect_dead_games,
+        ..GameConfig::default()
+    });
+    set_board(&mut game, ["KH", "KD"], ["9H", "9C"], &["5S", "5C"], deck);
+    game.settle_outcome();
+    game
+}
+
+#[test]
+fn deck_that_can_never_unblock_anyone_is_a_draw() {
+    // Kings and nines only fit on queens, aces, eights and tens, and none are left to flip
+    let game = blocked_board(true, "2S 2H 5D 6S 3C 4D");
+    assert_eq!(game.outcome(), Some(GameOutcome::Draw));
+    assert_eq!(game.deck.len(), 6);
+    
+    // Flipping is left to play out unless the check is enabled
+    assert_eq!(blocked_board(false, "2S 2H 5D 6S 3C 4D").outcome(), None);
+}
+
+#[test]
+fn deck_with_a_card_that_unblocks_someone_plays_on() {
+    let game = blocked_board(true, "2S 2H 5D 8S 3C 4D");
+    assert_eq!(game.outcome(), None);
+    assert!(game.progress_possible());
+}
+
+#[test]
+fn asking_rejects_a_card_that_fits_two_piles() {
+    let [first, _] = player_ids();
+    let play = |game: &mut GameState, pile_index, pile_selection| {
+        game.process_command(GameCommand {
+            player_id: first,
+            action: PlayerAction::PlayCard { card_index: 0, pile_index },
+            pile_selection,
+        })
+    };
+    let mut game = started_game(GameConfig::default());
+    set_board(&mut game, ["5H 8C", "KC"], ["", ""], &["4S", "6D"], "2C");
+    
+    assert_eq!(play(&mut game, None, PileSelection::Ask).unwrap_err(), ActionError::AmbiguousPile);
+    assert_eq!(game.players[0].hand, cards("5H 8C"));
+    assert_eq!(game.validate_move_with(first, 0, None, PileSelection::FirstLegal), Ok(0));
+    assert_eq!(play(&mut game, Some(1), PileSelection::Ask).unwrap().pile_index, Some(1));
+    
+    // The same card is played on the first pile it fits when the server chooses
+    set_board(&mut game, ["5H 8C", "KC"], ["", ""], &["4S", "6D"], "2C");
+    assert_eq!(play(&mut game, None, PileSelection::FirstLegal).unwrap().pile_index, Some(0));
+    assert_eq!(game.center_piles[0], cards("4S 5H"));
+}
+
+#[test]
+fn each_action_is_only_taken_in_its_phases() {
+    let [first, second] = player_ids();
+    let in_phase = |phase| {
+        let mut game = match phase {
+            GamePhase::Waiting => GameState::new(GameConfig::default()),
+            GamePhase::Ready => seated_game(GameConfig::default()),
+            _ => started_game(GameConfig::default()),
+        };
+        game.add_player(first);
+        match phase {
+            GamePhase::Paused => game.transition_to(GamePhase::Paused).unwrap(),
+            GamePhase::Over => assert!(game.forfeit(second)),
+            _ => {}
+        }
+        assert_eq!(game.phase(), phase);
+        game
+    };
+    let actions = [
+        PlayerAction::PlayCard { card_index: 0, pile_index: None },
+        PlayerAction::RequestNewCenterCards,
+        PlayerAction::RequestRedeal,
+        PlayerAction::ClearPile { pile_index: 0 },
+    ];
+    
+    for phase in [GamePhase::Waiting, GamePhase::Ready, GamePhase::Playing, GamePhase::Paused, GamePhase::Over] {
+        for action in &actions {
+            let kind = action.kind();
+            assert_eq!(kind.allowed_in(phase), phase == GamePhase::Playing, "{:?} in {:?}", kind, phase);
+            
+            // Other rules may still turn an action down, but never for the phase it came in
+            let result = act(&mut in_phase(phase), first, action.clone());
+            let refused = ActionError::ActionNotAllowedInPhase { phase, action: kind };
+            assert_eq!(result.err() == Some(refused), !kind.allowed_in(phase), "{:?} in {:?}", kind, phase);
+        }
+    }
+}
+
+#[test]
+fn seat_is_handed_over_with_its_cards_only_while_paused() {
+    let [first, second] = player_ids();
+    let newcomer = Uuid::from_u128(3);
+    let mut game = started_game(GameConfig::default());
+    assert!(!game.hand_over_seat(first, newcomer));
+    
+    game.transition_to(GamePhase::Paused).unwrap();
+    let hand = game.players[0].hand.clone();
+    assert!(!game.hand_over_seat(first, second));
+    assert!(game.hand_over_seat(first, newcomer));
+    assert_eq!(game.players[0].id, newcomer);
+    assert_eq!(game.players[0].seat, 0);
+    assert_eq!(game.players[0].hand, hand);
+    assert!(!game.hand_over_seat(first, Uuid::from_u128(4)));
+    
+    // The newcomer plays on from where the departed player left off
+    game.transition_to(GamePhase::Playing).unwrap();
+    act(&mut game, newcomer, PlayerAction::RequestNewCenterCards).unwrap();
+    assert_eq!(act(&mut game, first, PlayerAction::RequestNewCenterCards), Err(ActionError::PlayerNotFound));
+}
+
+#[test]
+fn names_can_only_be_changed_while_seats_are_open() {
+    let [first, second] = player_ids();
+    let mut game = GameState::new(GameConfig::default());
+    game.add_player(first);
+    assert_eq!(game.rename_player(first, "Ann".to_string()), Ok(()));
+    assert_eq!(game.rename_player(second, "Bea".to_string()), Err(ActionError::PlayerNotFound));
+    
+    game.add_player(second);
+    assert_eq!(
+        game.rename_player(second, "Bea".to_string()),
+        Err(ActionError::ActionNotAllowedInPhase { phase: GamePhase::Ready, action: ActionKind::SetName })
+    );
+    assert_eq!(game.players[0].name.as_deref(), Some("Ann"));
+    assert_eq!(game.players[1].name, None);
+}
+
+#[test]
+fn rematch_is_agreed_once_every_player_asks_after_the_game() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig::default());
+    assert_eq!(
+        game.request_rematch(first),
+        Err(ActionError::ActionNotAllowedInPhase { phase: GamePhase::Playing, action: ActionKind::Rematch })
+    );
+    
+    assert!(game.forfeit(second));
+    assert_eq!(game.request_rematch(Uuid::from_u128(3)), Err(ActionError::PlayerNotFound));
+    assert_eq!(game.request_rematch(first), Ok(false));
+    assert_eq!(game.request_rematch(first), Err(ActionError::RematchAlreadyRequested));
+    assert_eq!(game.request_rematch(second), Ok(true));
+}
+
+#[test]
+fn views_carry_match_scores_only_when_scoring() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        point_target: Some(50),
+        ..GameConfig::default()
+    });
+    let loser = &game.players[1];
+    let left = (loser.hand.len() + loser.draw_pile.len()) as u32;
+    assert!(game.forfeit(second));
+    let view = game.create_player_view(first);
+    assert_eq!((view.score, view.opponent_score), (Some(left), Some(0)));
+    
+    let game = started_game(GameConfig::default());
+    assert_eq!(game.create_player_view(first).score, None);
+}
+
+#[test]
+fn move_limit_goes_to_the_player_with_fewest_cards() {
+    let [first, second] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_moves: Some(1),
+        ..GameConfig::default()
+    });
+    game.players[1].draw_pile.pop_back();
+    
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    assert_eq!(game.outcome(), Some(GameOutcome::Win(second)));
+    
+    // Every view names the winner, not just the outcome
+    assert_eq!(game.winner, Some(second));
+    assert_eq!(game.create_player_view(first).winner, Some(second));
+    assert_eq!(game.create_observer_view().winning_seat, Some(1));
+    assert_eq!(game.create_spectator_view().winning_seat, Some(1));
+}
+
+#[test]
+fn move_limit_with_equal_cards_is_a_draw() {
+    let [first, _] = player_ids();
+    let mut game = started_game(GameConfig {
+        max_moves: Some(1),
+        ..GameConfig::default()
+    });
+    
+    act(&mut game, first, PlayerAction::RequestNewCenterCards).unwrap();
+    assert_eq!(game.outcome(), Some(GameOutcome::Draw));
+    assert_eq!(game.winner, None);
+}
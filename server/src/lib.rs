@@ -0,0 +1,6 @@
+pub mod cache;
+pub mod env;
+pub mod game;
+pub mod simulate;
+pub mod webhook;
+pub mod websocket;
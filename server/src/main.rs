@@ -1,7 +1,7 @@
-mod game;
-mod websocket;
-
 use log::info;
+use speed_game_server::game::GameConfig;
+use speed_game_server::websocket::ServerConfig;
+use speed_game_server::{simulate, websocket};
 use std::env;
 use tokio::net::TcpListener;
 
@@ -10,6 +10,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logger
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("debug"));
     
+    // Load game rules
+    let config = GameConfig::from_env();
+    
+    // Run a single simulated game instead of the server if requested
+    let args: Vec<String> = env::args().collect();
+    if let Some(position) = args.iter().position(|arg| arg == "--simulate") {
+        let seed = match args.get(position + 1).map(|value| value.parse::<u64>()) {
+            Some(Ok(seed)) => seed,
+            _ => return Err("--simulate requires a numeric seed".into()),
+        };
+        
+        simulate::run_simulation(config, seed);
+        return Ok(());
+    }
+    
     // Set up WebSocket server
     let addr = env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
     info!("Starting Speed game server on {}", addr);
@@ -17,8 +32,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let listener = TcpListener::bind(&addr).await?;
     info!("WebSocket server listening on: {}", addr);
     
+    info!("Game configuration: {:?}", config);
+    let server_config = ServerConfig::from_env();
+    
     // Accept and handle connections
-    websocket::run_websocket_server(listener).await?;
+    websocket::run_websocket_server(listener, config, server_config).await?;
     
     Ok(())
 }
@@ -0,0 +1,131 @@
+use uuid::Uuid;
+
+use crate::game::{GameCommand, GameConfig, GameOutcome, GameState, PileSelection, PlayerAction};
+
+// Play a full game between two greedy players and print every move, returning the winner
+pub fn run_simulation(config: GameConfig, seed: u64) -> Option<Uuid> {
+    let config = GameConfig {
+        seed: Some(seed),
+        ..config
+    };
+    
+    // Fixed player ids keep the output identical for the same seed
+    let player_ids = [Uuid::from_u128(1), Uuid::from_u128(2)];
+    
+    let mut game = GameState::new(config);
+    for &id in &player_ids {
+        game.add_player(id);
+    }
+    if let Err(e) = game.start_game() {
+        println!("Could not start the game: {}", e);
+        return None;
+    }
+    
+    println!("Simulating game with seed {}", seed);
+    println!("{}", game.describe());
+    println!();
+    
+    // Players are labelled by seat, which needn't follow the order they were added in
+    let seating: Vec<(usize, Uuid)> = game.players.iter().map(|p| (p.seat, p.id)).collect();
+    
+    let mut turn = 1;
+    while !game.is_game_over() {
+        // Each player plays their first playable card, if they have one
+        let mut anyone_played = false;
+        for &(seat, id) in &seating {
+            let card_index = match game.find_playable_card(id) {
+                Some(index) => index,
+                None => continue,
+            };
+            let card = match game.players.iter().find(|p| p.id == id) {
+                Some(player) => player.hand[card_index],
+                None => continue,
+            };
+            
+            let command = GameCommand {
+                player_id: id,
+                action: PlayerAction::PlayCard { card_index, pile_index: None },
+                pile_selection: PileSelection::FirstLegal,
+            };
+            if let Ok(result) = game.process_command(command) {
+                let pile = result.pile_index.map_or(0, |index| index + 1);
+                println!("{:>4}. Player {} plays {} on pile {}", turn, seat + 1, card, pile);
+                turn += 1;
+                anyone_played = true;
+            }
+            
+            if game.is_game_over() {
+                break;
+            }
+        }
+        
+        if anyone_played {
+            continue;
+        }
+        
+        // Nobody can move, so flip new center cards
+        let command = GameCommand {
+            player_id: player_ids[0],
+            action: PlayerAction::RequestNewCenterCards,
+            pile_selection: PileSelection::FirstLegal,
+        };
+        match game.process_command(command) {
+            Ok(_) => {
+                println!("{:>4}. Both players are stuck, flipping new center cards", turn);
+                turn += 1;
+            }
+            Err(e) => {
+                println!("No moves left and {}", e);
+                break;
+            }
+        }
+    }
+    
+    println!();
+    println!("{}", game.describe());
+    
+    for (seat, stats) in game.stats().players.iter().enumerate() {
+        println!(
+            "Player {}: {} cards played, {} flips requested, efficiency {:.2}",
+            seat + 1,
+            stats.cards_played,
+            stats.flips_requested,
+            stats.efficiency
+        );
+    }
+    
+    match game.outcome() {
+        Some(GameOutcome::Win(winner)) => Some(winner),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn randomized_seats_are_simulated_by_player_id() {
+        for seed in 0..32 {
+            let config = GameConfig {
+                randomize_seats: true,
+                ..GameConfig::default()
+            };
+            if let Some(winner) = run_simulation(config, seed) {
+                assert!([Uuid::from_u128(1), Uuid::from_u128(2)].contains(&winner));
+            }
+        }
+    }
+    
+    #[test]
+    fn simulation_runs_to_a_winner() {
+        assert_eq!(run_simulation(GameConfig::default(), 1), Some(Uuid::from_u128(2)));
+        assert_eq!(run_simulation(GameConfig::default(), 2), Some(Uuid::from_u128(1)));
+    }
+    
+    #[test]
+    fn same_seed_gives_the_same_result() {
+        let first = run_simulation(GameConfig::default(), 7);
+        assert_eq!(run_simulation(GameConfig::default(), 7), first);
+    }
+}
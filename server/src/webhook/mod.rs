@@ -0,0 +1,213 @@
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use uuid::Uuid;
+
+use crate::game::{GameOutcome, GameState, GameStats};
+
+// Attempts made to deliver a summary before giving up
+const MAX_ATTEMPTS: u32 = 3;
+// Wait before the first retry, doubled after each failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+// Summary of a finished game posted to the outcome webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct OutcomeSummary {
+    // Player ids in seat order
+    pub players: Vec<Uuid>,
+    pub outcome: GameOutcome,
+    pub stats: GameStats,
+    // Seed the deal was shuffled from, so the game can be reproduced
+    pub seed: Option<u64>,
+}
+
+impl OutcomeSummary {
+    // Summarize a game, or `None` while it is still being played
+    pub fn from_game(game: &GameState) -> Option<Self> {
+        Some(OutcomeSummary {
+            players: game.players.iter().map(|p| p.id).collect(),
+            outcome: game.outcome()?,
+            stats: game.stats(),
+            seed: game.deal_reveal().map(|deal| deal.seed),
+        })
+    }
+}
+
+// Post a summary to the webhook in the background, retrying with backoff. Each attempt is
+// bounded by the timeout, so a slow endpoint only delays its own delivery, never the game.
+pub fn spawn_post(url: String, summary: OutcomeSummary, timeout: Duration) {
+    tokio::spawn(async move {
+        let body = match serde_json::to_string(&summary) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to serialize outcome summary: {}", e);
+                return;
+            }
+        };
+        
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = match tokio::time::timeout(timeout, post_json(&url, &body)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("timed out after {:?}", timeout)),
+            };
+            
+            match result {
+                Ok(()) => {
+                    info!("Posted game outcome to webhook");
+                    return;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    warn!("Webhook attempt {} failed: {}, retrying in {:?}", attempt, e, backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(e) => error!("Giving up on webhook after {} attempts: {}", attempt, e),
+            }
+        }
+    });
+}
+
+// Send a JSON body with a plain HTTP/1.1 POST, succeeding on any 2xx status
+async fn post_json(url: &str, body: &str) -> Result<(), String> {
+    let (host, path) = parse_http_url(url)?;
+    let authority = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    
+    let mut stream = TcpStream::connect(&authority).await.map_err(|e| e.to_string())?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+    
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| e.to_string())?;
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    debug!("Webhook responded with {:?}", status_line);
+    
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(format!("unexpected response {:?}", status_line)),
+    }
+}
+
+// Split an `http://host[:port]/path` URL into its host and path; TLS isn't supported
+fn parse_http_url(url: &str) -> Result<(&str, &str), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("only http:// webhook URLs are supported, got {}", url))?;
+    
+    match rest.find('/') {
+        Some(index) => Ok((&rest[..index], &rest[index..])),
+        None => Ok((rest, "/")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameConfig, PlayerStats};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    
+    // An HTTP endpoint answering each request with the next of these statuses, passing on the
+    // request bodies it receives
+    async fn mock_endpoint(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks/outcome", listener.local_addr().unwrap());
+        let (bodies, received) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut stream).await;
+                assert!(request.starts_with("POST /hooks/outcome HTTP/1.1\r\n"), "unexpected request {:?}", request);
+                let body = request.split_once("\r\n\r\n").unwrap().1.to_string();
+                let _ = bodies.send(body);
+                let response = format!("HTTP/1.1 {} Whatever\r\nContent-Length: 0\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, received)
+    }
+    
+    // Read a request up to the end of the body its Content-Length announces
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or(0);
+                if body.len() >= length || read == 0 {
+                    return text;
+                }
+            }
+        }
+    }
+    
+    fn summary() -> OutcomeSummary {
+        let winner = Uuid::from_u128(1);
+        OutcomeSummary {
+            players: vec![winner, Uuid::from_u128(2)],
+            outcome: GameOutcome::Win(winner),
+            stats: GameStats {
+                players: vec![PlayerStats::new(winner, 20, 2), PlayerStats::new(Uuid::from_u128(2), 15, 3)],
+            },
+            seed: Some(99),
+        }
+    }
+    
+    #[test]
+    fn urls_split_into_host_and_path() {
+        assert_eq!(parse_http_url("http://example.com:8080/a/b"), Ok(("example.com:8080", "/a/b")));
+        assert_eq!(parse_http_url("http://example.com"), Ok(("example.com", "/")));
+        assert!(parse_http_url("https://example.com/").is_err());
+    }
+    
+    #[test]
+    fn summaries_are_only_made_for_finished_games() {
+        let mut game = GameState::new(GameConfig::default());
+        game.add_player(Uuid::from_u128(1));
+        game.add_player(Uuid::from_u128(2));
+        game.start_game().unwrap();
+        assert!(OutcomeSummary::from_game(&game).is_none());
+        
+        game.forfeit(Uuid::from_u128(2));
+        let summary = OutcomeSummary::from_game(&game).unwrap();
+        assert_eq!(summary.outcome, GameOutcome::Win(Uuid::from_u128(1)));
+        assert_eq!(summary.seed, game.deal_reveal().map(|deal| deal.seed));
+    }
+    
+    #[tokio::test]
+    async fn summary_is_posted_as_json() {
+        let (url, mut received) = mock_endpoint(vec![204]).await;
+        spawn_post(url, summary(), Duration::from_secs(5));
+        
+        let body: serde_json::Value = serde_json::from_str(&received.recv().await.unwrap()).unwrap();
+        assert_eq!(body, serde_json::to_value(summary()).unwrap());
+        assert_eq!(body["seed"], 99);
+        assert_eq!(body["stats"]["players"][0]["cards_played"], 20);
+    }
+    
+    #[tokio::test]
+    async fn failed_posts_are_retried() {
+        let (url, mut received) = mock_endpoint(vec![500, 200]).await;
+        spawn_post(url, summary(), Duration::from_secs(5));
+        
+        let first = received.recv().await.unwrap();
+        let second = received.recv().await.unwrap();
+        assert_eq!(first, second);
+    }
+}
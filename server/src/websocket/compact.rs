@@ -0,0 +1,274 @@
+use uuid::Uuid;
+
+use crate::game::{Card, GameOutcome, PlayerView};
+
+// Compact binary layout of a `PlayerView` for low-bandwidth clients. After a three byte header
+// (`S`, `V`, version) the fields follow in a fixed order:
+//
+// - flags byte: game started, sudden death, opponent requested a redeal, paused
+// - player id as 16 bytes
+// - hand: varint length, then one byte per card (see `Card::to_byte`)
+// - draw pile, opponent hand and opponent draw pile counts as varints
+// - center piles: varint pile count, then each pile as a varint length and card bytes
+// - playable piles: varint count, then the flags packed eight per byte, lowest bit first
+// - outcome byte: 0 none, 1 win followed by the 16 byte winner id, 2 draw, 3 stalemate
+//
+// Game provenance, the challenge date, the opponent's name, match scores and projector extras are left out;
+// clients that need them should use the regular encodings.
+const MAGIC: [u8; 2] = *b"SV";
+pub const COMPACT_VERSION: u8 = 1;
+
+const FLAG_GAME_STARTED: u8 = 1;
+const FLAG_SUDDEN_DEATH: u8 = 1 << 1;
+const FLAG_OPPONENT_REQUESTED_REDEAL: u8 = 1 << 2;
+const FLAG_PAUSED: u8 = 1 << 3;
+
+const OUTCOME_NONE: u8 = 0;
+const OUTCOME_WIN: u8 = 1;
+const OUTCOME_DRAW: u8 = 2;
+const OUTCOME_STALEMATE: u8 = 3;
+
+// Encode a view in the compact layout
+pub fn encode_view(view: &PlayerView) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64);
+    out.extend_from_slice(&MAGIC);
+    out.push(COMPACT_VERSION);
+    
+    let mut flags = 0;
+    if view.game_started {
+        flags |= FLAG_GAME_STARTED;
+    }
+    if view.sudden_death {
+        flags |= FLAG_SUDDEN_DEATH;
+    }
+    if view.opponent_requested_redeal {
+        flags |= FLAG_OPPONENT_REQUESTED_REDEAL;
+    }
+    if view.paused {
+        flags |= FLAG_PAUSED;
+    }
+    out.push(flags);
+    out.extend_from_slice(view.player_id.as_bytes());
+    
+    write_cards(&mut out, &view.hand);
+    write_varint(&mut out, view.draw_pile_count as u64);
+    write_varint(&mut out, view.opponent_hand_count as u64);
+    write_varint(&mut out, view.opponent_draw_pile_count as u64);
+    
+    write_varint(&mut out, view.center_piles.len() as u64);
+    for pile in &view.center_piles {
+        write_cards(&mut out, pile);
+    }
+    
+    write_varint(&mut out, view.playable_piles.len() as u64);
+    for chunk in view.playable_piles.chunks(8) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (i, &playable)| bits | ((playable as u8) << i));
+        out.push(bits);
+    }
+    
+    match view.outcome {
+        None => out.push(OUTCOME_NONE),
+        Some(GameOutcome::Win(winner)) => {
+            out.push(OUTCOME_WIN);
+            out.extend_from_slice(winner.as_bytes());
+        }
+        Some(GameOutcome::Draw) => out.push(OUTCOME_DRAW),
+        Some(GameOutcome::Stalemate) => out.push(OUTCOME_STALEMATE),
+    }
+    
+    out
+}
+
+// Decode a view from the compact layout
+pub fn decode_view(bytes: &[u8]) -> Result<PlayerView, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    
+    if reader.take(2)? != MAGIC {
+        return Err("not a compact view".to_string());
+    }
+    let version = reader.byte()?;
+    if version != COMPACT_VERSION {
+        return Err(format!("unsupported compact view version {}", version));
+    }
+    
+    let flags = reader.byte()?;
+    let player_id = reader.uuid()?;
+    let hand = reader.cards()?;
+    let draw_pile_count = reader.varint()? as usize;
+    let opponent_hand_count = reader.varint()? as usize;
+    let opponent_draw_pile_count = reader.varint()? as usize;
+    
+    let pile_count = reader.varint()? as usize;
+    let center_piles = (0..pile_count)
+        .map(|_| reader.cards())
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let playable_count = reader.varint()? as usize;
+    let packed = reader.take(playable_count.div_ceil(8))?;
+    let playable_piles = (0..playable_count)
+        .map(|i| packed[i / 8] & (1 << (i % 8)) != 0)
+        .collect();
+    
+    let outcome = match reader.byte()? {
+        OUTCOME_NONE => None,
+        OUTCOME_WIN => Some(GameOutcome::Win(reader.uuid()?)),
+        OUTCOME_DRAW => Some(GameOutcome::Draw),
+        OUTCOME_STALEMATE => Some(GameOutcome::Stalemate),
+        other => return Err(format!("unknown outcome tag {}", other)),
+    };
+    let winner = match outcome {
+        Some(GameOutcome::Win(winner)) => Some(winner),
+        _ => None,
+    };
+    
+    Ok(PlayerView {
+        player_id,
+        hand,
+        draw_pile_count,
+        opponent_hand_count,
+        opponent_draw_pile_count,
+        opponent_name: None,
+        score: None,
+        opponent_score: None,
+        center_piles,
+        playable_piles,
+        opponent_requested_redeal: flags & FLAG_OPPONENT_REQUESTED_REDEAL != 0,
+        game_started: flags & FLAG_GAME_STARTED != 0,
+        sudden_death: flags & FLAG_SUDDEN_DEATH != 0,
+        paused: flags & FLAG_PAUSED != 0,
+        winner,
+        outcome,
+        game_provenance: None,
+        challenge_date: None,
+        extras: Default::default(),
+    })
+}
+
+fn write_cards(out: &mut Vec<u8>, cards: &[Card]) {
+    write_varint(out, cards.len() as u64);
+    out.extend(cards.iter().map(Card::to_byte));
+}
+
+// LEB128: seven bits per byte, lowest group first, high bit set while more bytes follow
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Cursor over an encoded view
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        match end {
+            Some(end) => {
+                let slice = &self.bytes[self.pos..end];
+                self.pos = end;
+                Ok(slice)
+            }
+            None => Err("compact view ended early".to_string()),
+        }
+    }
+    
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    
+    fn uuid(&mut self) -> Result<Uuid, String> {
+        let bytes = self.take(16)?;
+        Uuid::from_slice(bytes).map_err(|e| e.to_string())
+    }
+    
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint is too long".to_string())
+    }
+    
+    fn cards(&mut self) -> Result<Vec<Card>, String> {
+        let len = self.varint()? as usize;
+        self.take(len)?
+            .iter()
+            .map(|&byte| Card::from_byte(byte).ok_or_else(|| format!("invalid card byte {}", byte)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameConfig, GameState};
+    
+    // A dealt game's view for the first player, with pile hints so every field is filled in
+    fn dealt_view() -> PlayerView {
+        let mut game = GameState::new(GameConfig {
+            seed: Some(3),
+            pile_hints: true,
+            ..GameConfig::default()
+        });
+        game.add_player(Uuid::from_u128(1));
+        game.add_player(Uuid::from_u128(2));
+        game.start_game().unwrap();
+        game.create_player_view(Uuid::from_u128(1))
+    }
+    
+    // The parts of a view the compact layout carries
+    fn comparable(view: &PlayerView) -> serde_json::Value {
+        let mut view = view.clone();
+        view.game_provenance = None;
+        serde_json::to_value(view).unwrap()
+    }
+    
+    #[test]
+    fn views_round_trip() {
+        let mut view = dealt_view();
+        assert_eq!(comparable(&decode_view(&encode_view(&view)).unwrap()), comparable(&view));
+        
+        view.paused = true;
+        view.sudden_death = true;
+        view.playable_piles = vec![true, false, true, true, false, false, true, false, true];
+        for outcome in [GameOutcome::Win(Uuid::from_u128(2)), GameOutcome::Draw, GameOutcome::Stalemate] {
+            view.outcome = Some(outcome);
+            view.winner = match outcome {
+                GameOutcome::Win(winner) => Some(winner),
+                _ => None,
+            };
+            assert_eq!(comparable(&decode_view(&encode_view(&view)).unwrap()), comparable(&view));
+        }
+    }
+    
+    #[test]
+    fn compact_views_are_much_smaller_than_json() {
+        let view = dealt_view();
+        let compact = encode_view(&view).len();
+        let json = serde_json::to_vec(&view).unwrap().len();
+        assert!(compact * 5 < json, "compact view is {} bytes, JSON is {}", compact, json);
+    }
+    
+    #[test]
+    fn damaged_views_are_rejected() {
+        let bytes = encode_view(&dealt_view());
+        assert!(decode_view(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_view(b"JSON").is_err());
+        
+        let mut newer = bytes.clone();
+        newer[2] = COMPACT_VERSION + 1;
+        assert!(decode_view(&newer).unwrap_err().contains("version"));
+    }
+}
@@ -0,0 +1,250 @@
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::projector::{IdentityProjector, ViewProjector};
+use crate::env;
+
+// Server configuration
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    // How often to log connection metrics; disabled if unset
+    pub metrics_log_interval: Option<Duration>,
+    // How long both players must stay connected before the game starts
+    pub start_debounce: Duration,
+    // Whether the game waits for both players to send `Ready` before it is dealt
+    pub require_ready: bool,
+    // Minimum time between activity notices relayed to a player's opponent
+    pub activity_debounce: Duration,
+    // How long a game stays paused after a player disconnects before it is reset
+    pub disconnect_grace: Duration,
+    // How many action idempotency keys are remembered per player
+    pub idempotency_keys: usize,
+    // How long an action idempotency key is remembered; until evicted if unset
+    pub idempotency_ttl: Option<Duration>,
+    // How long a session token can be used to reconnect after it's issued; until evicted if unset
+    pub session_ttl: Option<Duration>,
+    // Whether players are only paired with opponents connecting from a different address
+    pub require_distinct_clients: bool,
+    // Malformed or unknown messages a client may send before being disconnected; unlimited if unset
+    pub max_protocol_violations: Option<u32>,
+    // Whether a client disconnected for violations also forfeits a game in progress
+    pub forfeit_on_violations: bool,
+    // How long a connection may go without a game state before the current one is resent; disabled if unset
+    pub idle_resend_interval: Option<Duration>,
+    // HTTP URL a summary of every finished game is posted to; disabled if unset
+    pub outcome_webhook: Option<String>,
+    // How long each attempt to post to the outcome webhook may take
+    pub webhook_timeout: Duration,
+    // Hook applied to every player's view before it is sent; set in code rather than the environment
+    pub view_projector: Arc<dyn ViewProjector>,
+    // Frames that may wait to be written to a client before newer game states are dropped
+    pub outgoing_queue: usize,
+    // Oldest client version allowed to stay connected, in dotted numeric form like `1.4.0`; any if unset
+    pub min_client_version: Option<String>,
+    // Where clients below the minimum version can get a newer one, included in the upgrade notice
+    pub client_download_url: Option<String>,
+    // Break between the rounds of a best-of-N match before the next one is dealt, and after a
+    // forfeit before the departed player's seat is reopened
+    pub round_break: Duration,
+    // Countdown after a game ends before a rematch with the same players is dealt, looping until
+    // someone leaves; disabled if unset
+    pub auto_rematch: Option<Duration>,
+    // Most games that may run at once; players arriving when every room is taken spectate a game in
+    // progress, or are turned away if there is none. Unlimited if unset
+    pub max_rooms: Option<usize>,
+    // Whether arriving players are matched with whoever is waiting straight away; otherwise they
+    // start in a private room of their own until they ask to be matched
+    pub match_on_connect: bool,
+    // What spectators are shown of the games they watch, unless a private room's creator chose otherwise
+    pub spectator_access: SpectatorAccess,
+    // Whether a seat left open when a game is reset goes to the spectator who has watched longest,
+    // unless they declined
+    pub promote_spectators: bool,
+    // Which game a promoted spectator plays when a player doesn't come back to a game in progress
+    pub spectator_handoff: SpectatorHandoff,
+    // Most spectators watching each room's game at once; unlimited if unset
+    pub max_spectators_per_room: Option<usize>,
+}
+
+// How much of a game its spectators are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum SpectatorAccess {
+    // Every state of the game as it's played
+    #[default]
+    Live,
+    // Only the result once the game is over, so play can't be relayed to either player
+    EndOnly,
+}
+
+// How a spectator is promoted into the seat of a player who left a game in progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpectatorHandoff {
+    // The departed player forfeits and the spectator is dealt into a new game, so nothing they
+    // saw while watching helps them
+    #[default]
+    FreshGame,
+    // The spectator takes over the departed player's cards and the game carries on, even though
+    // they watched both hands being played
+    ContinueGame,
+}
+
+impl FromStr for SpectatorHandoff {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fresh_game" => Ok(SpectatorHandoff::FreshGame),
+            "continue_game" => Ok(SpectatorHandoff::ContinueGame),
+            _ => Err(format!("unknown spectator handoff: {}", s)),
+        }
+    }
+}
+
+impl FromStr for SpectatorAccess {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "live" => Ok(SpectatorAccess::Live),
+            "end_only" => Ok(SpectatorAccess::EndOnly),
+            _ => Err(format!("unknown spectator access: {}", s)),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            metrics_log_interval: None,
+            start_debounce: Duration::from_millis(250),
+            require_ready: false,
+            activity_debounce: Duration::from_millis(1000),
+            disconnect_grace: Duration::from_millis(2000),
+            idempotency_keys: 32,
+            idempotency_ttl: Some(Duration::from_secs(60)),
+            session_ttl: Some(Duration::from_secs(2 * 60 * 60)),
+            require_distinct_clients: false,
+            max_protocol_violations: Some(20),
+            forfeit_on_violations: false,
+            idle_resend_interval: None,
+            outcome_webhook: None,
+            webhook_timeout: Duration::from_millis(5000),
+            view_projector: Arc::new(IdentityProjector),
+            outgoing_queue: 64,
+            min_client_version: None,
+            client_download_url: None,
+            round_break: Duration::from_millis(5000),
+            auto_rematch: None,
+            max_rooms: None,
+            match_on_connect: true,
+            spectator_access: SpectatorAccess::Live,
+            promote_spectators: false,
+            spectator_handoff: SpectatorHandoff::FreshGame,
+            max_spectators_per_room: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    // Load the configuration from environment variables, falling back to defaults
+    pub fn from_env() -> Self {
+        Self::from_vars(&env::process)
+    }
+    
+    // Like `from_env`, reading each setting through `lookup` instead
+    pub fn from_vars(lookup: env::Lookup) -> Self {
+        let defaults = ServerConfig::default();
+        
+        let metrics_log_interval = env::var_opt(lookup, "SPEED_METRICS_LOG_SECS")
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let start_debounce = env::var_opt(lookup, "SPEED_START_DEBOUNCE_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.start_debounce);
+        let require_ready = env::var_or(lookup, "SPEED_REQUIRE_READY", defaults.require_ready);
+        let activity_debounce = env::var_opt(lookup, "SPEED_ACTIVITY_DEBOUNCE_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.activity_debounce);
+        let disconnect_grace = env::var_opt(lookup, "SPEED_DISCONNECT_GRACE_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.disconnect_grace);
+        let idempotency_keys = env::var_or(lookup, "SPEED_IDEMPOTENCY_KEYS", defaults.idempotency_keys);
+        // Zero disables expiry, leaving only the capacity bound
+        let idempotency_ttl = match env::var_opt(lookup, "SPEED_IDEMPOTENCY_TTL_SECS") {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => defaults.idempotency_ttl,
+        };
+        let session_ttl = match env::var_opt(lookup, "SPEED_SESSION_TTL_SECS") {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => defaults.session_ttl,
+        };
+        let require_distinct_clients =
+            env::var_or(lookup, "SPEED_REQUIRE_DISTINCT_CLIENTS", defaults.require_distinct_clients);
+        // Zero lifts the limit
+        let max_protocol_violations = match env::var_opt(lookup, "SPEED_MAX_PROTOCOL_VIOLATIONS") {
+            Some(0) => None,
+            Some(limit) => Some(limit),
+            None => defaults.max_protocol_violations,
+        };
+        let forfeit_on_violations = env::var_or(lookup, "SPEED_FORFEIT_ON_VIOLATIONS", defaults.forfeit_on_violations);
+        let idle_resend_interval = env::var_opt(lookup, "SPEED_IDLE_RESEND_SECS")
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let outcome_webhook = env::var_opt::<String>(lookup, "SPEED_OUTCOME_WEBHOOK").filter(|url| !url.is_empty());
+        let webhook_timeout = env::var_opt(lookup, "SPEED_WEBHOOK_TIMEOUT_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.webhook_timeout);
+        // The channel needs room for at least one frame
+        let outgoing_queue = env::var_or(lookup, "SPEED_OUTGOING_QUEUE", defaults.outgoing_queue).max(1);
+        let min_client_version =
+            env::var_opt::<String>(lookup, "SPEED_MIN_CLIENT_VERSION").filter(|version| !version.is_empty());
+        let client_download_url =
+            env::var_opt::<String>(lookup, "SPEED_CLIENT_DOWNLOAD_URL").filter(|url| !url.is_empty());
+        let round_break = env::var_opt(lookup, "SPEED_ROUND_BREAK_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.round_break);
+        let auto_rematch = env::var_opt(lookup, "SPEED_AUTO_REMATCH_SECS")
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        let max_rooms = env::var_opt(lookup, "SPEED_MAX_ROOMS").filter(|&max| max > 0);
+        let match_on_connect = env::var_or(lookup, "SPEED_MATCH_ON_CONNECT", defaults.match_on_connect);
+        let spectator_access = env::var_or(lookup, "SPEED_SPECTATOR_ACCESS", defaults.spectator_access);
+        let promote_spectators = env::var_or(lookup, "SPEED_PROMOTE_SPECTATORS", defaults.promote_spectators);
+        let spectator_handoff = env::var_or(lookup, "SPEED_SPECTATOR_HANDOFF", defaults.spectator_handoff);
+        // Zero lifts the limit
+        let max_spectators_per_room = env::var_opt(lookup, "SPEED_MAX_SPECTATORS_PER_ROOM").filter(|&max| max > 0);
+        
+        ServerConfig {
+            metrics_log_interval,
+            start_debounce,
+            require_ready,
+            activity_debounce,
+            disconnect_grace,
+            idempotency_keys,
+            idempotency_ttl,
+            session_ttl,
+            require_distinct_clients,
+            max_protocol_violations,
+            forfeit_on_violations,
+            idle_resend_interval,
+            outcome_webhook,
+            webhook_timeout,
+            view_projector: defaults.view_projector,
+            outgoing_queue,
+            min_client_version,
+            client_download_url,
+            round_break,
+            auto_rematch,
+            max_rooms,
+            match_on_connect,
+            spectator_access,
+            promote_spectators,
+            spectator_handoff,
+            max_spectators_per_room,
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use uuid::Uuid;
+
+use super::SpectatorAccess;
+use crate::game::{
+    ActionResult, DealReveal, GameConfig, GameOutcome, GameStats, PileSelection, PlayerAction, PlayerStatus, PlayerView,
+    RevealedCards, Series, SpectatorView,
+};
+
+// Messages a client can send to the server
+#[derive(Debug, Clone, Deserialize)]
+pub enum ClientMessage {
+    // Identify the client build, usually sent right after connecting; clients older than the
+    // server's minimum are told to upgrade and disconnected
+    Hello { client_version: String },
+    // Leave the game being waited for and open a private room, answered with its code in a
+    // `RoomJoined` to share with a friend. Unless matching on connect is turned off, players are
+    // matched with whoever is waiting as soon as they connect, so clients meaning to play a
+    // friend should send this or `JoinRoom` first. With a `password`, only those who give it
+    // can join or watch the room. `spectators` overrides how much of the game its spectators
+    // are shown.
+    CreateRoom {
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        spectators: Option<SpectatorAccess>,
+    },
+    // Like `CreateRoom`, but the room plays matches of its own instead of the server's default:
+    // best of `best_of` rounds, or scored up to `point_target` points
+    CreateMatch {
+        #[serde(default)]
+        best_of: Option<u32>,
+        #[serde(default)]
+        point_target: Option<u32>,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        spectators: Option<SpectatorAccess>,
+    },
+    // Leave the game being waited for and join a friend's private room by its code, giving its
+    // password if it was created with one
+    JoinRoom {
+        code: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    // Take back a seat lost with a dropped connection, using the token from `Session`; sent
+    // first thing after connecting, before the game the new connection was put in starts
+    Reconnect { token: String },
+    // Leave the game being waited for, e.g. a private room, to be matched with the next player
+    // looking for an opponent; answered with `Queued` until there is one, then `MatchFound`
+    QueueForMatch,
+    // Watch a game in progress without playing: the private room with this code, or any public
+    // game if no code is given, with the room's password if it has one. Answered with
+    // `SpectatorState` for as long as the player watches.
+    Spectate {
+        code: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    // Choose the name the opponent is shown instead of the player's id; kept across games
+    SetName { name: String },
+    // Offer the opponent a rematch once the game is over; a new game is dealt as soon as both
+    // players have asked, and the offer is passed on with `RematchOffered` until then
+    RequestRematch,
+    // Say something to everyone in the room, players and spectators alike
+    Chat { text: String },
+    // Say something to the other spectators of the game being watched, which its players never see
+    SpectatorChat { text: String },
+    // Keep watching when a seat opens in the game being watched, rather than being promoted into
+    // it; lasts until the spectator moves to another room
+    DeclinePromotion,
+    // The client has loaded and is ready to play; if the server requires it, the game is dealt
+    // once both players are ready. Only accepted from players before the game is dealt, and lasts
+    // for the rest of the connection, so later games start without another one.
+    Ready,
+    // Ask to be moved to a specific seat before the game starts
+    JoinSeat { seat: usize },
+    // Latency probe answered immediately with a `Pong`
+    Ping { nonce: u64 },
+    // Choose how views are rendered for this connection; omitted options are turned off.
+    // Center piles are trimmed to their top `center_pile_depth` cards, one by default, unless
+    // `full_center_piles` asks for every card
+    SetViewOptions {
+        #[serde(default)]
+        full_center_piles: bool,
+        #[serde(default)]
+        center_pile_depth: Option<usize>,
+        #[serde(default)]
+        compact_views: bool,
+    },
+    // Choose how plays that don't name a pile are resolved for this connection, usually sent
+    // right after connecting; the first legal pile is used until then
+    SetPileSelection { mode: PileSelection },
+    // Ask what the player can currently do, answered with a `Status`
+    GetStatus,
+    // Heartbeat telling the opponent this player is active without taking an action
+    Active,
+    // An action tagged with a client-chosen key, so a retry is acknowledged without being applied twice
+    KeyedAction { action: PlayerAction, idempotency_key: String },
+    // Plain game actions are accepted untagged for compatibility with older clients; `decode`
+    // tells them apart from the tagged messages above by their type name
+    #[serde(skip)]
+    Action(PlayerAction),
+}
+
+// Type names of the tagged messages above, so a malformed message of a known type can be told
+// apart from one of a type this server doesn't know
+const MESSAGE_TYPES: &[&str] = &[
+    "Hello",
+    "CreateRoom",
+    "CreateMatch",
+    "JoinRoom",
+    "Reconnect",
+    "QueueForMatch",
+    "Spectate",
+    "SetName",
+    "RequestRematch",
+    "Chat",
+    "SpectatorChat",
+    "DeclinePromotion",
+    "Ready",
+    "JoinSeat",
+    "Ping",
+    "SetViewOptions",
+    "SetPileSelection",
+    "GetStatus",
+    "Active",
+    "KeyedAction",
+];
+
+// Type names of the plain game actions sent untagged
+const ACTION_TYPES: &[&str] = &["PlayCard", "RequestNewCenterCards", "RequestRedeal", "ClearPile"];
+
+// Why a data frame couldn't be turned into a `ClientMessage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    // Not valid JSON or MessagePack at all
+    Malformed(String),
+    // A message of a type this server doesn't know, e.g. from a newer protocol version
+    Unknown(String),
+    // A message of a known type whose contents don't fit it, e.g. a `PlayCard` without a card
+    Invalid { name: String, error: String },
+}
+
+// Encoding of a message on the wire, chosen per frame: text frames carry JSON, binary frames MessagePack
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl ClientMessage {
+    // Decode a data frame in the format its frame type implies, or `None` for control frames
+    pub fn decode(msg: &Message) -> Option<(WireFormat, Result<Self, DecodeError>)> {
+        let (format, value) = match msg {
+            Message::Text(text) => (WireFormat::Json, serde_json::from_str(text)),
+            Message::Binary(bytes) => {
+                let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes).with_human_readable();
+                (
+                    WireFormat::MessagePack,
+                    serde_json::Value::deserialize(&mut deserializer).map_err(serde::de::Error::custom),
+                )
+            }
+            _ => return None,
+        };
+        let message = match value {
+            Ok(value) => Self::from_value(&value),
+            Err(e) => Err(DecodeError::Malformed(e.to_string())),
+        };
+        Some((format, message))
+    }
+    
+    // Parse a message by its type name first, so a known type that doesn't parse is reported with
+    // what's wrong with it rather than as unknown
+    fn from_value(value: &serde_json::Value) -> Result<Self, DecodeError> {
+        let name = Self::type_name(value).unwrap_or("<unnamed>");
+        let invalid = |e: serde_json::Error| DecodeError::Invalid {
+            name: name.to_string(),
+            error: e.to_string(),
+        };
+        if MESSAGE_TYPES.contains(&name) {
+            Self::deserialize(value)
+                .or_else(|e| match value {
+                    // Messages whose fields are all optional may be sent as just their name
+                    serde_json::Value::String(_) => Self::deserialize(serde_json::json!({ name: {} })).map_err(|_| e),
+                    _ => Err(e),
+                })
+                .map_err(invalid)
+        } else if ACTION_TYPES.contains(&name) {
+            PlayerAction::deserialize(value).map(ClientMessage::Action).map_err(invalid)
+        } else {
+            Err(DecodeError::Unknown(name.to_string()))
+        }
+    }
+    
+    // Name of a message's type, if it has the usual `{ "Type": ... }` or `"Type"` shape
+    fn type_name(value: &serde_json::Value) -> Option<&str> {
+        match value {
+            serde_json::Value::Object(map) if map.len() == 1 => map.keys().next().map(String::as_str),
+            serde_json::Value::String(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+// Pre-encoded error sent when a message can't be serialized, so it can never fail itself
+pub const SERIALIZATION_FAILED_JSON: &str =
+    r#"{"type":"Error","code":"serialization_failed","message":"The server could not encode this update"}"#;
+
+// A seated player as shown in a `Lobby`
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyPlayer {
+    pub player_id: Uuid,
+    pub seat: usize,
+    pub name: Option<String>,
+    pub ready: bool,
+}
+
+// Messages the server sends to a client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    // The rules in effect, sent before the first game state of each game
+    GameConfig { config: GameConfig },
+    // The player's current view of the game
+    GameState(PlayerView),
+    // What a spectator sees of the game they're watching
+    SpectatorState(SpectatorView),
+    // Every room is taken; if `can_spectate`, the player was put in to watch a game in progress
+    RoomFull { can_spectate: bool },
+    // The game the player asked to watch, or every game they could have been put in to watch
+    // when all rooms are taken, already has as many spectators as a room allows
+    SpectatorsFull,
+    // The player is now in the private room with this code, waiting for the game to start
+    RoomJoined { code: String },
+    // Token for taking this player's seat back with `Reconnect` if the connection drops; good
+    // for one reconnect, which is answered with a new token, until the game is reset without
+    // them or the token expires
+    Session { token: String },
+    // The player is waiting to be matched with an opponent
+    Queued,
+    // The player was matched with an opponent and the game will start shortly; sent to both
+    MatchFound,
+    // The player's seat request was accepted
+    SeatAssigned { seat: usize },
+    // The spectator was given this seat when it opened in the game they were watching, and is
+    // sent player views from now on. Depending on the server's handoff, they're dealt into a
+    // new game or carry on with the cards of the player who left.
+    Promoted { seat: usize },
+    // Reply to a `Ping`, with the server's clock in milliseconds since the Unix epoch
+    Pong { nonce: u64, server_time: u64 },
+    // The game has ended, with how each player did, the deal behind the provenance commitment and,
+    // if the variant reveals them, everyone's remaining cards; sent after the final game state
+    GameOver {
+        outcome: GameOutcome,
+        stats: GameStats,
+        deal: Option<DealReveal>,
+        revealed: Option<Vec<RevealedCards>>,
+    },
+    // The deck is exhausted and the next successful play wins
+    SuddenDeath,
+    // The opponent just did something, in milliseconds since the Unix epoch
+    OpponentActive { at: u64 },
+    // Standings after a round of a longer match, with the match winner once there is one;
+    // sent after `GameOver` in games that are part of a longer match
+    RoundOver { series: Series, match_winner: Option<Uuid> },
+    // A player offered a rematch of the game that just ended; sent to everyone in the room,
+    // the player included
+    RematchOffered { player_id: Uuid },
+    // A chat message from someone in the room, which may be the player themselves; `name` is
+    // the sender's display name if they chose one
+    Chat { from: Uuid, name: Option<String>, spectator: bool, text: String, at: u64 },
+    // A message from a spectator to the others watching, which may be the player themselves;
+    // only sent to spectators
+    SpectatorChat { from: Uuid, name: Option<String>, text: String, at: u64 },
+    // Who is seated in the game being waited for and whether they're ready, sent to the room
+    // whenever that changes before the game starts
+    Lobby { players: Vec<LobbyPlayer> },
+    // Reply to `GetStatus`
+    Status(PlayerStatus),
+    // The player's action was applied; sent before the resulting game state
+    Ack(ActionResult),
+    // A request could not be fulfilled
+    Error { code: String, message: String },
+    // A rematch with the same players will be dealt after this many milliseconds unless someone
+    // leaves; sent after `GameOver` when auto-rematch is enabled
+    RematchScheduled { delay_ms: u64 },
+    // The client is older than the server supports and is about to be disconnected, with where
+    // to get a newer one if the server knows
+    UpgradeRequired { min_version: String, download_url: Option<String> },
+}
+
+impl ServerMessage {
+    // Encode the message as a frame in the given format
+    pub fn encode(&self, format: WireFormat) -> Result<Message, String> {
+        match format {
+            WireFormat::Json => serde_json::to_string(self)
+                .map(Message::Text)
+                .map_err(|e| e.to_string()),
+            // Struct maps keep field names, which the internally tagged enum needs, and the
+            // human-readable flag keeps ids as strings like they are in JSON
+            WireFormat::MessagePack => {
+                let mut bytes = Vec::new();
+                let mut serializer = rmp_serde::Serializer::new(&mut bytes)
+                    .with_struct_map()
+                    .with_human_readable();
+                self.serialize(&mut serializer)
+                    .map(|_| Message::Binary(bytes))
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+    
+    // Error sent in place of a message that couldn't be serialized, in the given format. It holds
+    // nothing but fixed strings, so encoding it doesn't fail the way the original message did.
+    pub fn serialization_failed(format: WireFormat) -> Message {
+        let json = Message::Text(SERIALIZATION_FAILED_JSON.to_string());
+        match format {
+            WireFormat::Json => json,
+            WireFormat::MessagePack => serde_json::from_str::<serde_json::Value>(SERIALIZATION_FAILED_JSON)
+                .ok()
+                .and_then(|fallback| rmp_serde::to_vec_named(&fallback).ok())
+                .map_or(json, Message::Binary),
+        }
+    }
+    
+    // Build an error message from a machine-readable code and a human-readable description
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        ServerMessage::Error {
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Counters tracking how clients connect and disconnect over the server's lifetime
+#[derive(Debug)]
+pub struct ConnectionMetrics {
+    started_at: Instant,
+    connects: AtomicU64,
+    disconnects: AtomicU64,
+    total_session_millis: AtomicU64,
+    messages_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+// Point-in-time summary of the connection metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct ChurnSnapshot {
+    pub connects: u64,
+    pub disconnects: u64,
+    pub active_connections: u64,
+    pub connects_per_minute: f64,
+    pub disconnects_per_minute: f64,
+    pub average_session_secs: f64,
+    // Messages sent to clients and their encoded size, to measure bandwidth
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub average_message_bytes: f64,
+}
+
+impl ConnectionMetrics {
+    pub fn new() -> Self {
+        ConnectionMetrics {
+            started_at: Instant::now(),
+            connects: AtomicU64::new(0),
+            disconnects: AtomicU64::new(0),
+            total_session_millis: AtomicU64::new(0),
+            messages_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+        }
+    }
+    
+    // Record a newly established connection
+    pub fn record_connect(&self) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    // Record a closed connection and how long it lasted
+    pub fn record_disconnect(&self, session: Duration) {
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+        self.total_session_millis
+            .fetch_add(session.as_millis() as u64, Ordering::Relaxed);
+    }
+    
+    // Record a message sent to a client and its encoded size in bytes
+    pub fn record_sent(&self, bytes: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+    
+    // Summarize the counters, including rates since the server started
+    pub fn snapshot(&self) -> ChurnSnapshot {
+        let connects = self.connects.load(Ordering::Relaxed);
+        let disconnects = self.disconnects.load(Ordering::Relaxed);
+        let total_session_millis = self.total_session_millis.load(Ordering::Relaxed);
+        let messages_sent = self.messages_sent.load(Ordering::Relaxed);
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        
+        // Avoid dividing by zero right after startup
+        let uptime_minutes = (self.started_at.elapsed().as_secs_f64() / 60.0).max(f64::EPSILON);
+        let average_session_secs = if disconnects > 0 {
+            total_session_millis as f64 / disconnects as f64 / 1000.0
+        } else {
+            0.0
+        };
+        let average_message_bytes = if messages_sent > 0 {
+            bytes_sent as f64 / messages_sent as f64
+        } else {
+            0.0
+        };
+        
+        ChurnSnapshot {
+            connects,
+            disconnects,
+            active_connections: connects.saturating_sub(disconnects),
+            connects_per_minute: connects as f64 / uptime_minutes,
+            disconnects_per_minute: disconnects as f64 / uptime_minutes,
+            average_session_secs,
+            messages_sent,
+            bytes_sent,
+            average_message_bytes,
+        }
+    }
+}
+
+impl Default for ConnectionMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn connects_and_disconnects_update_the_churn_counters() {
+        let metrics = ConnectionMetrics::new();
+        metrics.record_connect();
+        metrics.record_connect();
+        metrics.record_disconnect(Duration::from_secs(3));
+        
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.connects, 2);
+        assert_eq!(snapshot.disconnects, 1);
+        assert_eq!(snapshot.active_connections, 1);
+        assert_eq!(snapshot.average_session_secs, 3.0);
+        assert!(snapshot.connects_per_minute > snapshot.disconnects_per_minute);
+    }
+    
+    #[test]
+    fn fresh_metrics_report_zeroes() {
+        let snapshot = ConnectionMetrics::new().snapshot();
+        assert_eq!(snapshot.active_connections, 0);
+        assert_eq!(snapshot.average_session_secs, 0.0);
+        assert_eq!(snapshot.connects_per_minute, 0.0);
+    }
+    
+    #[test]
+    fn sent_messages_add_up_to_an_average_size() {
+        let metrics = ConnectionMetrics::new();
+        for bytes in [100, 300, 200] {
+            metrics.record_sent(bytes);
+        }
+        
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.messages_sent, 3);
+        assert_eq!(snapshot.bytes_sent, 600);
+        assert_eq!(snapshot.average_message_bytes, 200.0);
+        assert_eq!(ConnectionMetrics::new().snapshot().average_message_bytes, 0.0);
+    }
+}
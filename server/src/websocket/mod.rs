@@ -1,61 +1,411 @@
 use futures_util::{SinkExt, StreamExt};
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use std::collections::HashMap;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
+use std::fmt;
+use std::net::IpAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::{Mutex, MutexGuard};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio_tungstenite::{accept_async, tungstenite::protocol::Message};
 use uuid::Uuid;
 
-use crate::game::{GameCommand, GameState, PlayerAction, PlayerView};
+use crate::game::{
+    ActionError, ActionKind, ActionResult, GameCommand, GameConfig, GamePhase, GameState, PileSelection, PlayerAction,
+    PlayerView, SpectatorView,
+};
+use crate::webhook::{self, OutcomeSummary};
 
-// Type for a player's WebSocket sender
-type PlayerSender = futures_util::stream::SplitSink<
-    tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
-    tokio_tungstenite::tungstenite::protocol::Message,
->;
+pub mod compact;
+mod config;
+mod messages;
+mod metrics;
+mod password;
+mod projector;
+mod rooms;
+#[cfg(test)]
+mod tests;
 
-// Type for connected players
-type Players = Arc<Mutex<HashMap<Uuid, PlayerSender>>>;
+pub use config::{ServerConfig, SpectatorAccess, SpectatorHandoff};
+pub use metrics::{ChurnSnapshot, ConnectionMetrics};
+pub use projector::{IdentityProjector, ViewProjector};
 
-// Game state shared between all connections
+use messages::{ClientMessage, DecodeError, LobbyPlayer, ServerMessage, WireFormat};
+use rooms::{Destination, Room, RoomError, Rooms};
+
+// Longest display name a player may choose, in characters
+const MAX_NAME_LENGTH: usize = 20;
+// Longest chat message, in characters
+const MAX_CHAT_LENGTH: usize = 200;
+
+// Why a frame couldn't be queued for a client
+#[derive(Debug)]
+enum SendError {
+    // The client isn't reading fast enough and its outgoing queue is full
+    QueueFull,
+    // The connection's writer has stopped or the connection was closed
+    Closed,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::QueueFull => write!(f, "outgoing queue is full"),
+            SendError::Closed => write!(f, "connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+// A connected player's outgoing queue and per-connection preferences
+struct Connection {
+    // Frames waiting for the connection's writer task; `None` once the connection is being closed
+    sender: Option<mpsc::Sender<Message>>,
+    // Address the client connected from, if known
+    client_ip: Option<IpAddr>,
+    // Format of the last message the client sent, which messages to it are encoded in
+    format: WireFormat,
+    // How many top cards of each center pile views include; every card if unset
+    center_pile_depth: Option<usize>,
+    // Whether views are sent in the compact binary layout
+    compact_views: bool,
+    // When the connection was last sent a game state, on Tokio's clock like the resend timer
+    last_state_sent: tokio::time::Instant,
+    // Whether the connection is watching the room's game rather than playing in it
+    spectating: bool,
+    // Whether the connection is watching a room whose spectators are only shown the result
+    end_only: bool,
+    // When the connection started watching, so the longest waiting spectator is promoted first
+    watching_since: tokio::time::Instant,
+    // Whether the spectator asked not to be promoted into an open seat
+    declines_promotion: bool,
+    // Name the player chose with `SetName`, carried to each game they're seated in
+    name: Option<String>,
+    // Whether the client said it's ready to play
+    ready: bool,
+    // Server-wide metrics that sent bytes are counted in
+    metrics: Arc<ConnectionMetrics>,
+    // Hook applied to views before they are sent to this player
+    projector: Arc<dyn ViewProjector>,
+}
+
+impl Connection {
+    fn new(
+        sender: mpsc::Sender<Message>,
+        client_ip: Option<IpAddr>,
+        metrics: Arc<ConnectionMetrics>,
+        projector: Arc<dyn ViewProjector>,
+    ) -> Self {
+        Connection {
+            sender: Some(sender),
+            client_ip,
+            format: WireFormat::default(),
+            center_pile_depth: Some(1),
+            compact_views: false,
+            last_state_sent: tokio::time::Instant::now(),
+            spectating: false,
+            end_only: false,
+            watching_since: tokio::time::Instant::now(),
+            declines_promotion: false,
+            name: None,
+            ready: false,
+            metrics,
+            projector,
+        }
+    }
+    
+    // Queue a frame for the client without waiting, counting it in the bandwidth metrics. The
+    // writer task does the actual sending, so a slow client never holds up whoever holds the lock.
+    fn send(&mut self, frame: Message) -> Result<(), SendError> {
+        let sender = self.sender.as_ref().ok_or(SendError::Closed)?;
+        let bytes = frame.len();
+        sender.try_send(frame).map_err(|e| match e {
+            TrySendError::Full(_) => SendError::QueueFull,
+            TrySendError::Closed(_) => SendError::Closed,
+        })?;
+        self.metrics.record_sent(bytes);
+        Ok(())
+    }
+    
+    // Stop queuing frames; the writer sends what is already queued, then closes the connection
+    fn close(&mut self) {
+        self.sender = None;
+    }
+}
+
+// Type for the players connected to a room
+type Players = Arc<Mutex<HashMap<Uuid, Connection>>>;
+
+// Game state shared between the connections in a room. Every action goes through this lock, and
+// tokio's mutex is fair, so near-simultaneous actions are applied in the order they were received.
+//
+// Lock order: code that needs more than one lock takes the room list first, then a room's
+// `Players`, then its game state, and never takes an earlier one while holding a later one. Two
+// tasks taking them in opposite orders could each hold one and wait forever for the other.
 type SharedGameState = Arc<Mutex<GameState>>;
 
 // Helper function to send game state to a player
 async fn send_game_state_to_player(
     players: &Players,
     player_id: Uuid,
-    view: &PlayerView,
+    view: &TableView,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Serialize to JSON
-    let json = serde_json::to_string(view)?;
-    
-    // Get the player's sender
     let mut players_lock = players.lock().await;
-    if let Some(sender) = players_lock.get_mut(&player_id) {
-        // Send the message
-        sender.send(Message::Text(json)).await?;
+    
+    let connection = match players_lock.get_mut(&player_id) {
+        Some(connection) => connection,
+        None => return Ok(()),
+    };
+    
+    connection.last_state_sent = tokio::time::Instant::now();
+    
+    // Spectators always get their view whole and as a regular message, unless they may only see
+    // how the game ends
+    let mut view = match view {
+        TableView::Player(view) => PlayerView::clone(view),
+        TableView::Spectator(_) if connection.end_only => return Ok(()),
+        TableView::Spectator(view) => {
+            let message = ServerMessage::SpectatorState(view.clone());
+            return drop_if_queue_full(player_id, send_message_locked(&mut players_lock, player_id, &message).await);
+        }
+    };
+    connection.projector.project(player_id, &mut view);
+    
+    // Trim the center piles to the depth this connection asked for
+    if let Some(depth) = connection.center_pile_depth {
+        view.keep_pile_tops(depth);
+    }
+    
+    let result = if connection.compact_views {
+        connection.send(Message::Binary(compact::encode_view(&view))).map_err(Into::into)
+    } else {
+        send_message_locked(&mut players_lock, player_id, &ServerMessage::GameState(view)).await
+    };
+    drop_if_queue_full(player_id, result)
+}
+
+// A newer state supersedes this one, so a client too far behind just misses it
+fn drop_if_queue_full(
+    player_id: Uuid,
+    result: Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Err(e) if matches!(e.downcast_ref(), Some(SendError::QueueFull)) => {
+            warn!("Dropping a game state for slow player {}", player_id);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+// The game as one connection is shown it: a player's own view, or the view for spectators
+enum TableView {
+    Player(Box<PlayerView>),
+    Spectator(SpectatorView),
+}
+
+// Render the game for a connection, depending on whether it is playing or watching
+fn view_for(game: &GameState, id: Uuid, spectating: bool) -> TableView {
+    if spectating {
+        TableView::Spectator(game.create_spectator_view())
+    } else {
+        TableView::Player(Box::new(game.create_player_view(id)))
+    }
+}
+
+// Render the game for everyone connected to a room
+fn table_views(players: &HashMap<Uuid, Connection>, game: &GameState) -> Vec<(Uuid, TableView)> {
+    players
+        .iter()
+        .map(|(&id, connection)| (id, view_for(game, id, connection.spectating)))
+        .collect()
+}
+
+// Helper function to send game state to several players, logging failures
+async fn send_player_views(players: &Players, player_views: Vec<(Uuid, TableView)>) {
+    for (id, view) in player_views {
+        if let Err(e) = send_game_state_to_player(players, id, &view).await {
+            error!("Error sending game state to player {}: {}", id, e);
+        }
     }
+}
+
+// Resend the current game state to every connection that hasn't been sent one for a while,
+// so clients can tell a quiet game from a dead connection
+async fn resend_idle_views(players: &Players, game_state: &SharedGameState, interval: Duration) {
+    let players_lock = players.lock().await;
+    let game = game_state.lock().await;
+    let player_views: Vec<_> = players_lock
+        .iter()
+        .filter(|(_, connection)| connection.last_state_sent.elapsed() >= interval)
+        .map(|(&id, connection)| (id, view_for(&game, id, connection.spectating)))
+        .collect();
+    drop(game);
+    drop(players_lock);
+    
+    send_player_views(players, player_views).await;
+}
+
+// Check every room for idle connections in the background, resending their views
+fn spawn_idle_resends(rooms: Arc<Rooms>, interval: Duration) {
+    // Checking more often than the interval keeps resends from drifting to twice the interval apart
+    let check_every = interval / 4;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + check_every, check_every);
+        loop {
+            ticker.tick().await;
+            for room in rooms.all().await {
+                resend_idle_views(&room.players, &room.game_state, interval).await;
+            }
+        }
+    });
+}
+
+// Helper function to send a message to a player
+async fn send_message(
+    players: &Players,
+    player_id: Uuid,
+    message: &ServerMessage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut players_lock = players.lock().await;
+    send_message_locked(&mut players_lock, player_id, message).await
+}
+
+// Helper function to send a message to a player while already holding the players lock
+async fn send_message_locked(
+    players_lock: &mut HashMap<Uuid, Connection>,
+    player_id: Uuid,
+    message: &ServerMessage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Get the player's connection
+    let connection = match players_lock.get_mut(&player_id) {
+        Some(connection) => connection,
+        None => return Ok(()),
+    };
+    
+    // Serialize in the client's format
+    let encoded = message.encode(connection.format);
+    send_encoded(connection, player_id, encoded)
+}
+
+// Send an encoded message, falling back to a minimal error in the same format if it couldn't be
+// encoded so one bad message doesn't leave the client stale
+fn send_encoded(
+    connection: &mut Connection,
+    player_id: Uuid,
+    encoded: Result<Message, String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frame = match encoded {
+        Ok(frame) => frame,
+        Err(e) => {
+            error!("Failed to serialize message for player {}: {}", player_id, e);
+            connection.send(ServerMessage::serialization_failed(connection.format))?;
+            return Err(e.into());
+        }
+    };
+    
+    // Send the message
+    connection.send(frame)?;
     
     Ok(())
 }
 
-pub async fn run_websocket_server(listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+// Current time in milliseconds since the Unix epoch
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// A name as chosen by a player, trimmed, or `None` if it's empty, too long or holds control characters
+fn display_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    let valid = !name.is_empty() && name.chars().count() <= MAX_NAME_LENGTH && !name.chars().any(char::is_control);
+    valid.then(|| name.to_string())
+}
+
+// A chat message trimmed of surrounding whitespace, or `None` if nothing is left or it's too long.
+// Line breaks and other control characters are turned into spaces.
+fn chat_text(text: &str) -> Option<String> {
+    let text: String = text.trim().chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    let valid = !text.is_empty() && text.chars().count() <= MAX_CHAT_LENGTH;
+    valid.then_some(text)
+}
+
+// Error for a chat message `chat_text` turned down
+fn invalid_chat() -> ServerMessage {
+    ServerMessage::error(
+        "invalid_chat",
+        format!("Chat messages must be 1 to {} characters long", MAX_CHAT_LENGTH),
+    )
+}
+
+// Whether a dotted version like `1.4.0` is older than the minimum. Parts are compared as
+// numbers, missing parts count as zero and anything after the digits of a part, such as a
+// `-beta` suffix, is ignored.
+fn version_below(version: &str, minimum: &str) -> bool {
+    fn parts(version: &str) -> Vec<u64> {
+        version
+            .trim()
+            .trim_start_matches('v')
+            .split('.')
+            .map(|part| {
+                let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().unwrap_or(0)
+            })
+            .collect()
+    }
+    
+    let (version, minimum) = (parts(version), parts(minimum));
+    let len = version.len().max(minimum.len());
+    let padded = |parts: &[u64]| (0..len).map(|i| parts.get(i).copied().unwrap_or(0)).collect::<Vec<_>>();
+    padded(&version) < padded(&minimum)
+}
+
+pub async fn run_websocket_server(
+    listener: TcpListener,
+    config: GameConfig,
+    server_config: ServerConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Initialize shared state
-    let players: Players = Arc::new(Mutex::new(HashMap::new()));
-    let game_state = Arc::new(Mutex::new(GameState::new()));
+    let rooms = Arc::new(Rooms::new(config, &server_config));
+    let metrics = Arc::new(ConnectionMetrics::new());
+    let server_config = Arc::new(server_config);
+    
+    // Periodically log connection churn if configured
+    if let Some(interval) = server_config.metrics_log_interval {
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                info!("Connection metrics: {:?}", metrics.snapshot());
+            }
+        });
+    }
+    
+    // Keep idle clients up to date if configured
+    if let Some(interval) = server_config.idle_resend_interval {
+        spawn_idle_resends(rooms.clone(), interval);
+    }
     
     // Accept connections
     while let Ok((stream, addr)) = listener.accept().await {
         info!("New connection from: {}", addr);
         
         // Clone the shared state for this connection
-        let players_clone = players.clone();
-        let game_state_clone = game_state.clone();
+        let rooms_clone = rooms.clone();
+        let metrics_clone = metrics.clone();
+        let config_clone = server_config.clone();
         
         // Spawn a new task for each connection
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, players_clone, game_state_clone).await {
+            let client_ip = Some(addr.ip());
+            if let Err(e) = handle_connection(stream, client_ip, rooms_clone, metrics_clone, config_clone).await {
                 error!("Error handling connection: {}", e);
             }
         });
@@ -64,97 +414,1152 @@ pub async fn run_websocket_server(listener: TcpListener) -> Result<(), Box<dyn s
     Ok(())
 }
 
-async fn handle_connection(
-    stream: TcpStream,
-    players: Players,
-    game_state: SharedGameState,
-) -> Result<(), Box<dyn std::error::Error>> {
+// Check whether another seated player connected from the same address as this player
+fn shares_client_with_seated_player(
+    players: &HashMap<Uuid, Connection>,
+    game: &GameState,
+    player_id: Uuid,
+) -> bool {
+    let client_ip = match players.get(&player_id).and_then(|c| c.client_ip) {
+        Some(ip) => ip,
+        None => return false,
+    };
+    
+    game.players
+        .iter()
+        .filter(|p| p.id != player_id)
+        .any(|p| players.get(&p.id).and_then(|c| c.client_ip) == Some(client_ip))
+}
+
+// Let the player's opponents know they're active
+async fn notify_opponents_of_activity(players: &Players, player_id: Uuid) {
+    let message = ServerMessage::OpponentActive { at: unix_time_millis() };
+    
+    let mut players_lock = players.lock().await;
+    // Spectators aren't anyone's opponent
+    let opponents: Vec<Uuid> = players_lock
+        .iter()
+        .filter(|(&id, connection)| id != player_id && !connection.spectating)
+        .map(|(&id, _)| id)
+        .collect();
+    for id in opponents {
+        if let Err(e) = send_message_locked(&mut players_lock, id, &message).await {
+            error!("Error sending activity notice to player {}: {}", id, e);
+        }
+    }
+}
+
+// Pass a chat message on to everyone in the room, spectators and the sender included, so every
+// client shows the conversation in the same order
+async fn broadcast_chat(players: &Players, player_id: Uuid, text: String) {
+    let mut players_lock = players.lock().await;
+    let (name, spectator) = match players_lock.get(&player_id) {
+        Some(connection) => (connection.name.clone(), connection.spectating),
+        None => return,
+    };
+    let message = ServerMessage::Chat {
+        from: player_id,
+        name,
+        spectator,
+        text,
+        at: unix_time_millis(),
+    };
+    
+    let everyone: Vec<Uuid> = players_lock.keys().copied().collect();
+    for id in everyone {
+        if let Err(e) = send_message_locked(&mut players_lock, id, &message).await {
+            error!("Error sending chat to player {}: {}", id, e);
+        }
+    }
+}
+
+// Pass a spectator chat message on to the room's spectators only, the sender included, so nothing
+// said while watching can reach the players
+async fn broadcast_spectator_chat(players: &Players, player_id: Uuid, text: String) {
+    let mut players_lock = players.lock().await;
+    let name = match players_lock.get(&player_id) {
+        Some(connection) => connection.name.clone(),
+        None => return,
+    };
+    let message = ServerMessage::SpectatorChat {
+        from: player_id,
+        name,
+        text,
+        at: unix_time_millis(),
+    };
+    
+    let spectators: Vec<Uuid> = players_lock
+        .iter()
+        .filter(|(_, connection)| connection.spectating)
+        .map(|(&id, _)| id)
+        .collect();
+    for id in spectators {
+        if let Err(e) = send_message_locked(&mut players_lock, id, &message).await {
+            error!("Error sending spectator chat to player {}: {}", id, e);
+        }
+    }
+}
+
+// Summary of a finished game, or `None` while it is still being played
+fn game_over_message(game: &GameState) -> Option<ServerMessage> {
+    let outcome = game.outcome()?;
+    info!("Hidden state fingerprint at end: {}", game.deck_fingerprint());
+    
+    Some(ServerMessage::GameOver {
+        outcome,
+        stats: game.stats(),
+        deal: game.deal_reveal(),
+        revealed: game.revealed_cards(),
+    })
+}
+
+// Post the result of a game that just ended to the outcome webhook, if one is configured
+fn notify_outcome_webhook(config: &ServerConfig, game: &GameState) {
+    let url = match &config.outcome_webhook {
+        Some(url) => url.clone(),
+        None => return,
+    };
+    if let Some(summary) = OutcomeSummary::from_game(game) {
+        webhook::spawn_post(url, summary, config.webhook_timeout);
+    }
+}
+
+// Award the game to a player's opponent and tell everyone at the table, returning whether the
+// game was forfeited. With `only_if_away` the game must be paused with the player still gone
+// and an opponent still there, so a player who reconnected in time keeps playing and a game
+// everyone left is simply reset.
+async fn forfeit_game(
+    players: &Players,
+    game_state: &SharedGameState,
+    config: &Arc<ServerConfig>,
+    player_id: Uuid,
+    only_if_away: bool,
+) -> bool {
+    let players_lock = players.lock().await;
+    let mut game = game_state.lock().await;
+    if only_if_away {
+        let opponent_connected = game.players.iter().any(|p| p.id != player_id && players_lock.contains_key(&p.id));
+        if game.phase() != GamePhase::Paused || players_lock.contains_key(&player_id) || !opponent_connected {
+            return false;
+        }
+    }
+    if !game.forfeit(player_id) {
+        return false;
+    }
+    info!("Player {} forfeited the game", player_id);
+    
+    let player_views: Vec<_> = table_views(&players_lock, &game);
+    let game_over = game_over_message(&game);
+    notify_outcome_webhook(config, &game);
+    drop(game);
+    drop(players_lock);
+    
+    let recipients: Vec<Uuid> = player_views.iter().map(|(id, _)| *id).collect();
+    send_player_views(players, player_views).await;
+    
+    if let Some(game_over) = game_over {
+        announce_game_over(players, game_state, config, &recipients, &game_over).await;
+    }
+    true
+}
+
+// Tell everyone at the table how the game ended, then set up the rematch if they are kept playing
+async fn announce_game_over(
+    players: &Players,
+    game_state: &SharedGameState,
+    config: &Arc<ServerConfig>,
+    recipients: &[Uuid],
+    game_over: &ServerMessage,
+) {
+    info!("Game over: {:?}", game_over);
+    for &id in recipients {
+        if let Err(e) = send_message(players, id, game_over).await {
+            error!("Error sending game over to player {}: {}", id, e);
+        }
+    }
+    
+    // In a match of several rounds, tell everyone the standings and deal the next round unless the
+    // match was just won
+    let series = Some(game_state.lock().await.series()).filter(|series| series.is_match());
+    let mut next_round = false;
+    if let Some(series) = series {
+        let match_winner = series.winner();
+        next_round = match_winner.is_none();
+        
+        let standings = ServerMessage::RoundOver { series, match_winner };
+        for &id in recipients {
+            if let Err(e) = send_message(players, id, &standings).await {
+                error!("Error sending round result to player {}: {}", id, e);
+            }
+        }
+    }
+    
+    let countdown = if next_round { Some(config.round_break) } else { config.auto_rematch };
+    if let Some(countdown) = countdown {
+        let notice = ServerMessage::RematchScheduled { delay_ms: countdown.as_millis() as u64 };
+        for &id in recipients {
+            if let Err(e) = send_message(players, id, &notice).await {
+                error!("Error sending rematch notice to player {}: {}", id, e);
+            }
+        }
+        schedule_rematch(players, game_state, config, countdown);
+    }
+}
+
+// Start a rematch once the countdown has passed
+fn schedule_rematch(players: &Players, game_state: &SharedGameState, config: &Arc<ServerConfig>, countdown: Duration) {
+    info!("Rematch in {:?}", countdown);
+    
+    let players = players.clone();
+    let game_state = game_state.clone();
+    let config = config.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(countdown).await;
+        rematch_if_everyone_stayed(&players, &game_state, &config).await;
+    });
+}
+
+// Deal a new game to the same players if the last one is still over and nobody left. A player
+// who left has already caused a reset of their own.
+async fn rematch_if_everyone_stayed(players: &Players, game_state: &SharedGameState, config: &ServerConfig) {
+    let players_lock = players.lock().await;
+    let game = game_state.lock().await;
+    let all_connected = game.players.iter().all(|p| players_lock.contains_key(&p.id));
+    if game.phase() != GamePhase::Over || !all_connected {
+        debug!("Players changed before the rematch, not starting one");
+        return;
+    }
+    
+    info!("Starting rematch");
+    replace_game_keeping_seats(players, game_state, config, players_lock, game).await;
+}
+
+// Record a player's rematch request and pass the offer on, dealing the rematch once every
+// seated player has asked
+async fn request_rematch(players: &Players, game_state: &SharedGameState, config: &ServerConfig, player_id: Uuid) {
+    let mut players_lock = players.lock().await;
+    let mut game = game_state.lock().await;
+    match game.request_rematch(player_id) {
+        Ok(true) => {
+            info!("Everyone agreed to a rematch");
+            replace_game_keeping_seats(players, game_state, config, players_lock, game).await;
+        }
+        Ok(false) => {
+            drop(game);
+            info!("Player {} offered a rematch", player_id);
+            
+            let offer = ServerMessage::RematchOffered { player_id };
+            for id in players_lock.keys().copied().collect::<Vec<_>>() {
+                if let Err(e) = send_message_locked(&mut players_lock, id, &offer).await {
+                    error!("Error sending rematch offer to player {}: {}", id, e);
+                }
+            }
+        }
+        Err(e) => {
+            drop(game);
+            let response = ServerMessage::error(e.code(), e.to_string());
+            if let Err(e) = send_message_locked(&mut players_lock, player_id, &response).await {
+                error!("Error sending error to player {}: {}", player_id, e);
+            }
+        }
+    }
+}
+
+// Start a new game once the grace period after a player's disconnect has passed, unless they
+// came back or their game was already replaced in the meantime. Remaining players keep their
+// seats and waiting players fill the rest.
+async fn reset_game_after_disconnect(players: &Players, game_state: &SharedGameState, config: &ServerConfig, player_id: Uuid) {
+    let players_lock = players.lock().await;
+    let game = game_state.lock().await;
+    if players_lock.contains_key(&player_id) || game.phase() == GamePhase::Playing {
+        debug!("Player {} came back before the reset, not resetting", player_id);
+        return;
+    }
+    if !game.players.iter().any(|p| p.id == player_id) {
+        debug!("Game was replaced since player {} left, not resetting", player_id);
+        return;
+    }
+    
+    info!("Game reset due to player disconnect");
+    replace_game_keeping_seats(players, game_state, config, players_lock, game).await;
+}
+
+// Swap the game for a fresh one with the same rules and tell everyone. Connected players keep
+// the seats they had, waiting players fill the rest, and the game starts if the seats are full.
+async fn replace_game_keeping_seats(
+    players: &Players,
+    game_state: &SharedGameState,
+    config: &ServerConfig,
+    mut players_lock: MutexGuard<'_, HashMap<Uuid, Connection>>,
+    mut game: MutexGuard<'_, GameState>,
+) {
+    let game_config = game.config.clone();
+    let previous = std::mem::replace(&mut *game, GameState::new(game_config));
+    
+    // Keep remaining players in the seats they already had
+    for player in previous.players.iter().filter(|p| players_lock.contains_key(&p.id)) {
+        game.add_player_to_seat(player.id, player.seat);
+        game.set_player_name(player.id, player.name.clone());
+    }
+    
+    // Seat any players who were waiting for a free seat; already seated players and spectators
+    // are skipped
+    for (&id, connection) in players_lock.iter().filter(|(_, connection)| !connection.spectating) {
+        if config.require_distinct_clients && shares_client_with_seated_player(&players_lock, &game, id) {
+            continue;
+        }
+        if game.add_player(id) {
+            game.set_player_name(id, connection.name.clone());
+        }
+    }
+    
+    // Spectators fill whatever seats are still open, if they're promoted
+    let promoted = if config.promote_spectators {
+        promote_spectators(&mut players_lock, &mut game, config.require_distinct_clients)
+    } else {
+        Vec::new()
+    };
+    for (id, seat) in promoted {
+        info!("Spectator {} promoted to seat {}", id, seat);
+        if let Err(e) = send_message_locked(&mut players_lock, id, &ServerMessage::Promoted { seat }).await {
+            error!("Error sending promotion to player {}: {}", id, e);
+        }
+    }
+    
+    // The same players carry on their match, if it isn't over yet
+    if game.continue_series(&previous) {
+        info!("Round {} of the match", game.series().round);
+    }
+    
+    // Create player views
+    let player_views: Vec<_> = table_views(&players_lock, &game);
+    
+    // Drop locks before async operations
+    drop(game);
+    drop(players_lock);
+    
+    // Notify everyone still connected
+    send_player_views(players, player_views).await;
+    
+    // A waiting player may have filled an empty seat
+    start_when_ready(players, game_state, config).await;
+}
+
+// Spectators willing to be promoted into a seat, longest watching first
+fn promotion_candidates(players: &HashMap<Uuid, Connection>) -> Vec<Uuid> {
+    let mut candidates: Vec<_> = players
+        .iter()
+        .filter(|(_, connection)| connection.spectating && !connection.declines_promotion)
+        .map(|(&id, connection)| (connection.watching_since, id))
+        .collect();
+    candidates.sort();
+    candidates.into_iter().map(|(_, id)| id).collect()
+}
+
+// Seat the spectators who have watched longest in the game's open seats, skipping those who
+// declined, returning each one promoted with their seat
+fn promote_spectators(
+    players: &mut HashMap<Uuid, Connection>,
+    game: &mut GameState,
+    require_distinct_clients: bool,
+) -> Vec<(Uuid, usize)> {
+    let mut promoted = Vec::new();
+    for id in promotion_candidates(players) {
+        if require_distinct_clients && shares_client_with_seated_player(players, game, id) {
+            continue;
+        }
+        if !game.add_player(id) {
+            break;
+        }
+        let connection = match players.get_mut(&id) {
+            Some(connection) => connection,
+            None => continue,
+        };
+        connection.spectating = false;
+        connection.end_only = false;
+        game.set_player_name(id, connection.name.clone());
+        if let Some(player) = game.players.iter().find(|p| p.id == id) {
+            promoted.push((id, player.seat));
+        }
+    }
+    promoted
+}
+
+// Give the seat of a player who didn't come back to the paused game to the spectator who has
+// watched longest, who carries on with their cards. Returns whether anyone took it over.
+async fn hand_seat_to_spectator(room: &Room, config: &ServerConfig, player_id: Uuid) -> bool {
+    let mut players_lock = room.players.lock().await;
+    let mut game = room.game_state.lock().await;
+    if players_lock.contains_key(&player_id) || game.phase() != GamePhase::Paused {
+        return false;
+    }
+    
+    // Spectators sharing the opponent's address are passed over if players are kept apart
+    let newcomer = promotion_candidates(&players_lock).into_iter().find(|&id| {
+        !config.require_distinct_clients || !shares_client_with_seated_player(&players_lock, &game, id)
+    });
+    let newcomer = match newcomer {
+        Some(id) if game.hand_over_seat(player_id, id) => id,
+        _ => return false,
+    };
+    let connection = match players_lock.get_mut(&newcomer) {
+        Some(connection) => connection,
+        None => return false,
+    };
+    connection.spectating = false;
+    connection.end_only = false;
+    game.set_player_name(newcomer, connection.name.clone());
+    
+    let all_connected = game.players.iter().all(|p| players_lock.contains_key(&p.id));
+    if all_connected {
+        if let Err(e) = game.transition_to(GamePhase::Playing) {
+            debug!("Not resuming room {}: {}", room.id, e);
+        }
+    }
+    let seat = game.players.iter().find(|p| p.id == newcomer).map_or(0, |p| p.seat);
+    info!("Spectator {} took over seat {} from player {}", newcomer, seat, player_id);
+    drop(game);
+    
+    if let Err(e) = send_message_locked(&mut players_lock, newcomer, &ServerMessage::Promoted { seat }).await {
+        error!("Error sending promotion to player {}: {}", newcomer, e);
+    }
+    drop(players_lock);
+    
+    resume_after_reconnect(room, newcomer).await;
+    true
+}
+
+// Tell a newly seated player where they are: the code of a private room, or whether they were
+// matched with an opponent or are waiting for one. A match is announced to both players.
+async fn announce_seating(room: &Room, player_id: Uuid) {
+    let phase = room.game_state.lock().await.phase();
+    let (message, recipients) = match &room.code {
+        Some(code) => (ServerMessage::RoomJoined { code: code.clone() }, vec![player_id]),
+        None if phase == GamePhase::Ready => {
+            (ServerMessage::MatchFound, room.players.lock().await.keys().copied().collect())
+        }
+        None => (ServerMessage::Queued, vec![player_id]),
+    };
+    
+    for id in recipients {
+        if let Err(e) = send_message(&room.players, id, &message).await {
+            error!("Error sending seating notice to player {}: {}", id, e);
+        }
+    }
+}
+
+// Move a player to another room before their game starts, updating everyone in the room they
+// left and the one they joined. Returns the new room, or `None` if the player stays put.
+async fn switch_room(
+    rooms: &Rooms,
+    from: &Arc<Room>,
+    config: &ServerConfig,
+    player_id: Uuid,
+    destination: Destination,
+) -> Option<Arc<Room>> {
+    let room = match rooms.move_player(from, player_id, destination).await {
+        Ok(room) => room,
+        Err(e) => {
+            let response = ServerMessage::error(e.code(), e.to_string());
+            if let Err(e) = send_message(&from.players, player_id, &response).await {
+                error!("Error sending room error to player {}: {}", player_id, e);
+            }
+            return None;
+        }
+    };
+    
+    // Whoever was left behind is waiting for an opponent again
+    if !Arc::ptr_eq(&room, from) {
+        send_room_views(from).await;
+    }
+    
+    announce_seating(&room, player_id).await;
+    send_room_views(&room).await;
+    
+    if !Arc::ptr_eq(&room, from) {
+        start_when_ready(&from.players, &from.game_state, config).await;
+    }
+    start_when_ready(&room.players, &room.game_state, config).await;
+    
+    Some(room)
+}
+
+// Bring a reconnected player up to date: the rules if their game was already dealt, then the
+// current state for everyone, which shows the game resumed if it was waiting on them
+async fn resume_after_reconnect(room: &Room, player_id: Uuid) {
+    let rules = {
+        let game = room.game_state.lock().await;
+        game.phase().has_started().then(|| ServerMessage::GameConfig { config: game.config.public() })
+    };
+    if let Some(rules) = rules {
+        if let Err(e) = send_message(&room.players, player_id, &rules).await {
+            error!("Error sending game config to player {}: {}", player_id, e);
+        }
+    }
+    
+    send_room_views(room).await;
+}
+
+// Send the current state of a room's game to everyone connected to it
+async fn send_room_views(room: &Room) {
+    let players_lock = room.players.lock().await;
+    let game = room.game_state.lock().await;
+    let player_views: Vec<_> = table_views(&players_lock, &game);
+    drop(game);
+    drop(players_lock);
+    
+    send_player_views(&room.players, player_views).await;
+}
+
+// Show everyone in the room who is seated and ready while the game is being waited for, and
+// schedule the start once both seats are filled by ready players
+async fn start_when_ready(players: &Players, game_state: &SharedGameState, config: &ServerConfig) {
+    let mut players_lock = players.lock().await;
+    let game = game_state.lock().await;
+    if game.phase().has_started() {
+        return;
+    }
+    
+    let lobby = ServerMessage::Lobby {
+        players: game
+            .players
+            .iter()
+            .map(|p| LobbyPlayer {
+                player_id: p.id,
+                seat: p.seat,
+                name: p.name.clone(),
+                ready: players_lock.get(&p.id).is_some_and(|c| c.ready),
+            })
+            .collect(),
+    };
+    let start = game.phase() == GamePhase::Ready && everyone_ready(&players_lock, &game, config.require_ready);
+    drop(game);
+    
+    for id in players_lock.keys().copied().collect::<Vec<_>>() {
+        if let Err(e) = send_message_locked(&mut players_lock, id, &lobby).await {
+            error!("Error sending lobby to player {}: {}", id, e);
+        }
+    }
+    drop(players_lock);
+    
+    if start {
+        schedule_start(players, game_state, config);
+    }
+}
+
+// Whether every seated player has said they're ready, or readiness isn't required
+fn everyone_ready(players: &HashMap<Uuid, Connection>, game: &GameState, require_ready: bool) -> bool {
+    !require_ready || game.players.iter().all(|p| players.get(&p.id).is_some_and(|c| c.ready))
+}
+
+// Start the game after the debounce window, if both seats are still filled by then
+fn schedule_start(players: &Players, game_state: &SharedGameState, config: &ServerConfig) {
+    let debounce = config.start_debounce;
+    let require_ready = config.require_ready;
+    info!("Two players seated, starting game in {:?}", debounce);
+    
+    let players = players.clone();
+    let game_state = game_state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(debounce).await;
+        start_game_if_ready(&players, &game_state, require_ready).await;
+    });
+}
+
+// Start the game if both seats are still filled by connected, ready players
+async fn start_game_if_ready(players: &Players, game_state: &SharedGameState, require_ready: bool) {
+    let mut players_lock = players.lock().await;
+    let mut game = game_state.lock().await;
+    
+    // A player may have left during the debounce window, or another task already started the game
+    let all_connected = game.players.iter().all(|p| players_lock.contains_key(&p.id));
+    if game.phase() != GamePhase::Ready || !all_connected || !everyone_ready(&players_lock, &game, require_ready) {
+        debug!("Players changed before the game could start, not starting");
+        return;
+    }
+    
+    info!("Starting game");
+    
+    // Start the game, telling everyone at the table if it can't be dealt
+    if let Err(e) = game.start_game() {
+        error!("Could not start game: {}", e);
+        let response = ServerMessage::error(e.code(), e.to_string());
+        for id in players_lock.keys().copied().collect::<Vec<_>>() {
+            if let Err(e) = send_message_locked(&mut players_lock, id, &response).await {
+                error!("Error sending start error to player {}: {}", id, e);
+            }
+        }
+        return;
+    }
+    debug!("Initial board:\n{}", game.describe());
+    info!("Hidden state fingerprint at start: {}", game.deck_fingerprint());
+    
+    // Tell everyone the rules before the first state, leaving out the seed. Everyone is tried even
+    // if one send fails, so a player who dropped can't leave the other with half a start.
+    let rules = ServerMessage::GameConfig {
+        config: game.config.public(),
+    };
+    let mut failed = Vec::new();
+    for id in players_lock.keys().copied().collect::<Vec<_>>() {
+        if let Err(e) = send_message_locked(&mut players_lock, id, &rules).await {
+            error!("Error sending game config to player {}: {}", id, e);
+            failed.push(id);
+        }
+    }
+    
+    // Create player views
+    let player_views: Vec<_> = table_views(&players_lock, &game);
+    
+    // Drop locks before async operations
+    drop(game);
+    drop(players_lock);
+    
+    // Send initial game state to all players
+    for (id, view) in player_views {
+        if failed.contains(&id) {
+            continue;
+        }
+        if let Err(e) = send_game_state_to_player(players, id, &view).await {
+            error!("Error sending initial game state to player {}: {}", id, e);
+            failed.push(id);
+        }
+    }
+    
+    // Close the connections that couldn't be reached, so their handlers end and the usual
+    // disconnect handling pauses the game for the remaining player
+    let mut players_lock = players.lock().await;
+    for id in failed {
+        if let Some(connection) = players_lock.get_mut(&id) {
+            warn!("Closing connection to player {} after the game start couldn't be sent", id);
+            connection.close();
+        }
+    }
+}
+
+// Apply a player's action and broadcast the result, returning the acknowledged result if it was applied
+async fn apply_action(
+    players: &Players,
+    game_state: &SharedGameState,
+    config: &Arc<ServerConfig>,
+    player_id: Uuid,
+    action: PlayerAction,
+    pile_selection: PileSelection,
+) -> Option<ActionResult> {
+    let command = GameCommand {
+        player_id,
+        action,
+        pile_selection,
+    };
+    
+    // Update game state, skipping the broadcast if nothing changed. The players lock is taken
+    // first, following the lock order.
+    let mut players_lock = players.lock().await;
+    let mut game = game_state.lock().await;
+    let was_sudden_death = game.sudden_death;
+    let result = match game.process_command(command) {
+        Ok(result) => result,
+        Err(e) => {
+            debug!("Rejected action from player {}: {}", player_id, e);
+            drop(game);
+            
+            let response = ServerMessage::error(e.code(), e.to_string());
+            if let Err(e) = send_message_locked(&mut players_lock, player_id, &response).await {
+                error!("Error sending error to player {}: {}", player_id, e);
+            }
+            return None;
+        }
+    };
+    
+    // Create player views
+    let player_views = table_views(&players_lock, &game);
+    
+    // Actions are rejected once the game is over, so an outcome here means this action ended it
+    let game_over = game_over_message(&game);
+    if game_over.is_some() {
+        notify_outcome_webhook(config, &game);
+    }
+    let sudden_death_started = !was_sudden_death && game.sudden_death && game_over.is_none();
+    drop(game);
+    
+    // Acknowledge the action before releasing the players lock, so no state
+    // that includes this action can reach the player ahead of the ack
+    if let Err(e) = send_message_locked(&mut players_lock, player_id, &ServerMessage::Ack(result)).await {
+        error!("Error sending ack to player {}: {}", player_id, e);
+    }
+    
+    // Drop locks before async operations
+    drop(players_lock);
+    
+    // Send updated state to all players
+    let recipients: Vec<Uuid> = player_views.iter().map(|(id, _)| *id).collect();
+    send_player_views(players, player_views).await;
+    
+    // Warn both players that the next play decides the game
+    if sudden_death_started {
+        info!("Sudden death");
+        for &id in &recipients {
+            if let Err(e) = send_message(players, id, &ServerMessage::SuddenDeath).await {
+                error!("Error sending sudden death to player {}: {}", id, e);
+            }
+        }
+    }
+    
+    // Announce the result once the final state is out
+    if let Some(game_over) = game_over {
+        announce_game_over(players, game_state, config, &recipients, &game_over).await;
+    }
+    
+    Some(result)
+}
+
+// Drive a single client connection; generic over the stream so it can run on in-memory streams
+async fn handle_connection<S>(
+    stream: S,
+    client_ip: Option<IpAddr>,
+    rooms: Arc<Rooms>,
+    metrics: Arc<ConnectionMetrics>,
+    config: Arc<ServerConfig>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
     // Accept the WebSocket connection
     let ws_stream = accept_async(stream).await?;
     info!("WebSocket connection established");
     
-    // Generate a unique ID for this player
-    let player_id = Uuid::new_v4();
+    let connected_at = Instant::now();
+    metrics.record_connect();
+    
+    // Generate a unique ID for this player, replaced by their old one if they reconnect
+    let mut player_id = Uuid::new_v4();
     info!("Assigned player ID: {}", player_id);
     
     // Split the WebSocket stream
-    let (ws_sender, mut ws_receiver) = ws_stream.split();
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
     
-    // Add the player to our connected players
-    {
-        let mut players_lock = players.lock().await;
-        players_lock.insert(player_id, ws_sender);
-        
-        // If we have two players, start the game
-        if players_lock.len() == 2 {
-            info!("Two players connected, starting game");
-            
-            // Add players to the game
-            let mut game = game_state.lock().await;
-            for &id in players_lock.keys() {
-                game.add_player(id);
+    // Frames for this player are queued and written by a task of their own, so sending to a
+    // slow client never blocks anyone else
+    let (outgoing, mut outgoing_queue) = mpsc::channel(config.outgoing_queue);
+    tokio::spawn(async move {
+        while let Some(frame) = outgoing_queue.recv().await {
+            if let Err(e) = ws_sender.send(frame).await {
+                debug!("Error writing to player {}: {}", player_id, e);
+                return;
             }
-            
-            // Start the game
-            game.start_game();
-            
-            // Create player views
-            let player_views: Vec<_> = players_lock.keys().map(|&id| {
-                (id, game.create_player_view(id))
-            }).collect();
-            
-            // Drop locks before async operations
-            drop(game);
-            drop(players_lock);
-            
-            // Send initial game state to all players
-            for (id, view) in player_views {
-                send_game_state_to_player(&players, id, &view).await?;
+        }
+        let _ = ws_sender.close().await;
+    });
+    
+    // Seat the player in a room waiting for an opponent, or a private room of their own
+    let connection = Connection::new(outgoing.clone(), client_ip, metrics.clone(), config.view_projector.clone());
+    let mut room = match rooms.join(player_id, connection, config.match_on_connect).await {
+        Ok(room) => room,
+        Err(e) => {
+            warn!("Every room is taken, turning away player {}: {}", player_id, e);
+            let message = match e {
+                RoomError::SpectatorsFull => ServerMessage::SpectatorsFull,
+                _ => ServerMessage::RoomFull { can_spectate: false },
+            };
+            if let Ok(frame) = message.encode(WireFormat::Json) {
+                let _ = outgoing.try_send(frame);
             }
-        } else if players_lock.len() > 2 {
-            // We only support 2 players for now
-            warn!("More than 2 players connected, spectator mode not implemented");
-            // TODO: Implement spectator mode or waiting queue
+            metrics.record_disconnect(connected_at.elapsed());
+            return Ok(());
+        }
+    };
+    drop(outgoing);
+    let mut players = room.players.clone();
+    let mut game_state = room.game_state.clone();
+    let spectating = players.lock().await.get(&player_id).is_some_and(|c| c.spectating);
+    if spectating {
+        // Every room was taken, so the player was put in to watch a game instead
+        info!("Every room is taken, player {} is spectating", player_id);
+        if let Err(e) = send_message(&players, player_id, &ServerMessage::RoomFull { can_spectate: true }).await {
+            error!("Error sending room full notice to player {}: {}", player_id, e);
+        }
+        let view = view_for(&*game_state.lock().await, player_id, true);
+        if let Err(e) = send_game_state_to_player(&players, player_id, &view).await {
+            error!("Error sending game state to player {}: {}", player_id, e);
         }
+    } else {
+        announce_seating(&room, player_id).await;
     }
     
+    // Give the client a token to pick its seat back up with if the connection drops
+    let token = rooms.open_session(player_id).await;
+    if let Err(e) = send_message(&players, player_id, &ServerMessage::Session { token }).await {
+        error!("Error sending session token to player {}: {}", player_id, e);
+    }
+    
+    // If both seats are filled, start the game once everyone is ready
+    start_when_ready(&players, &game_state, &config).await;
+    
     // Handle incoming messages
+    let mut last_activity_notice: Option<Instant> = None;
+    let mut current_format = WireFormat::default();
+    let mut violations: u32 = 0;
+    let mut pile_selection = PileSelection::default();
     while let Some(result) = ws_receiver.next().await {
         match result {
             Ok(msg) => {
-                if msg.is_text() || msg.is_binary() {
-                    // Parse the message as a player action
-                    if let Ok(action) = serde_json::from_str::<PlayerAction>(msg.to_text()?) {
-                        // Process the action
-                        let command = GameCommand {
-                            player_id,
-                            action,
-                        };
-                        
-                        // Update game state
-                        let mut game = game_state.lock().await;
-                        game.process_command(command);
-                        
-                        // Create player views
-                        let mut player_views = Vec::new();
-                        let players_lock = players.lock().await;
-                        
-                        for &id in players_lock.keys() {
-                            player_views.push((id, game.create_player_view(id)));
+                // Parse data frames from the client in the format their frame type implies
+                if let Some((format, message)) = ClientMessage::decode(&msg) {
+                    // Answer in whichever format the client used last
+                    if format != current_format {
+                        current_format = format;
+                        if let Some(connection) = players.lock().await.get_mut(&player_id) {
+                            connection.format = format;
                         }
-                        
-                        // Drop locks before async operations
-                        drop(game);
-                        drop(players_lock);
-                        
-                        // Send updated state to all players
-                        for (id, view) in player_views {
-                            if let Err(e) = send_game_state_to_player(&players, id, &view).await {
-                                error!("Error sending game state to player {}: {}", id, e);
+                    }
+                    
+                    // Looked up for each message, since a spectator may have been promoted to a seat meanwhile
+                    let spectating = players.lock().await.get(&player_id).is_some_and(|c| c.spectating);
+                    
+                    // Relay activity to the opponent, at most once per debounce window
+                    let is_activity = matches!(
+                        message,
+                        Ok(ClientMessage::Action(_)) | Ok(ClientMessage::KeyedAction { .. }) | Ok(ClientMessage::Active)
+                    );
+                    
+                    // Spectators only watch; their actions are refused and they have no opponent
+                    if is_activity && spectating {
+                        if !matches!(message, Ok(ClientMessage::Active)) {
+                            let response = ServerMessage::error("spectating", "Spectators can't take actions");
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending error to player {}: {}", player_id, e);
+                            }
+                        }
+                        continue;
+                    }
+                    
+                    if is_activity && last_activity_notice.is_none_or(|at| at.elapsed() >= config.activity_debounce) {
+                        last_activity_notice = Some(Instant::now());
+                        notify_opponents_of_activity(&players, player_id).await;
+                    }
+                    
+                    match message {
+                        Ok(ClientMessage::CreateRoom { password, spectators }) => {
+                            let destination = Destination::NewPrivateRoom {
+                                best_of: None,
+                                point_target: None,
+                                password,
+                                spectators,
+                            };
+                            if let Some(moved_to) = switch_room(&rooms, &room, &config, player_id, destination).await {
+                                room = moved_to;
+                                players = room.players.clone();
+                                game_state = room.game_state.clone();
+                            }
+                        }
+                        Ok(ClientMessage::CreateMatch { best_of, point_target, password, spectators }) => {
+                            let destination = Destination::NewPrivateRoom { best_of, point_target, password, spectators };
+                            if let Some(moved_to) = switch_room(&rooms, &room, &config, player_id, destination).await {
+                                room = moved_to;
+                                players = room.players.clone();
+                                game_state = room.game_state.clone();
+                            }
+                        }
+                        Ok(ClientMessage::JoinRoom { code, password }) => {
+                            let destination = Destination::Code { code, password };
+                            if let Some(moved_to) = switch_room(&rooms, &room, &config, player_id, destination).await {
+                                room = moved_to;
+                                players = room.players.clone();
+                                game_state = room.game_state.clone();
+                            }
+                        }
+                        Ok(ClientMessage::Reconnect { token }) => {
+                            match rooms.reconnect(&room, player_id, &token).await {
+                                Ok((rejoined, id, token)) => {
+                                    info!("Connection of player {} now belongs to player {}", player_id, id);
+                                    let left = std::mem::replace(&mut room, rejoined);
+                                    player_id = id;
+                                    players = room.players.clone();
+                                    game_state = room.game_state.clone();
+                                    
+                                    if let Err(e) = send_message(&players, player_id, &ServerMessage::Session { token }).await {
+                                        error!("Error sending session token to player {}: {}", player_id, e);
+                                    }
+                                    send_room_views(&left).await;
+                                    resume_after_reconnect(&room, player_id).await;
+                                }
+                                Err(e) => {
+                                    let response = ServerMessage::error(e.code(), e.to_string());
+                                    if let Err(e) = send_message(&players, player_id, &response).await {
+                                        error!("Error sending error to player {}: {}", player_id, e);
+                                    }
+                                }
+                            }
+                        }
+                        Ok(ClientMessage::QueueForMatch) => {
+                            if let Some(moved_to) = switch_room(&rooms, &room, &config, player_id, Destination::Matchmaking).await {
+                                room = moved_to;
+                                players = room.players.clone();
+                                game_state = room.game_state.clone();
+                            }
+                        }
+                        Ok(ClientMessage::SetName { name }) => {
+                            let name = match display_name(&name) {
+                                Some(name) => name,
+                                None => {
+                                    let response = ServerMessage::error(
+                                        "invalid_name",
+                                        format!("Names must be 1 to {} characters long", MAX_NAME_LENGTH),
+                                    );
+                                    if let Err(e) = send_message(&players, player_id, &response).await {
+                                        error!("Error sending error to player {}: {}", player_id, e);
+                                    }
+                                    continue;
+                                }
+                            };
+                            
+                            let renamed = game_state.lock().await.rename_player(player_id, name.clone());
+                            match renamed {
+                                // A spectator's name is kept for the next game they're seated in
+                                Ok(()) | Err(ActionError::PlayerNotFound) => {
+                                    info!("Player {} is now called {:?}", player_id, name);
+                                    if let Some(connection) = players.lock().await.get_mut(&player_id) {
+                                        connection.name = Some(name);
+                                    }
+                                    // Show the opponent the new name straight away
+                                    if renamed.is_ok() {
+                                        send_room_views(&room).await;
+                                    }
+                                }
+                                Err(e) => {
+                                    let response = ServerMessage::error(e.code(), e.to_string());
+                                    if let Err(e) = send_message(&players, player_id, &response).await {
+                                        error!("Error sending error to player {}: {}", player_id, e);
+                                    }
+                                }
+                            }
+                        }
+                        Ok(ClientMessage::Ready) => {
+                            // Spectators may get ready for when they're promoted into a seat
+                            let phase = game_state.lock().await.phase();
+                            if !spectating && !ActionKind::Ready.allowed_in(phase) {
+                                let e = ActionError::ActionNotAllowedInPhase { phase, action: ActionKind::Ready };
+                                let response = ServerMessage::error(e.code(), e.to_string());
+                                if let Err(e) = send_message(&players, player_id, &response).await {
+                                    error!("Error sending error to player {}: {}", player_id, e);
+                                }
+                                continue;
+                            }
+                            if let Some(connection) = players.lock().await.get_mut(&player_id) {
+                                connection.ready = true;
                             }
+                            start_when_ready(&players, &game_state, &config).await;
+                        }
+                        Ok(ClientMessage::RequestRematch) => {
+                            request_rematch(&players, &game_state, &config, player_id).await;
+                        }
+                        Ok(ClientMessage::Chat { text }) => match chat_text(&text) {
+                            Some(text) => broadcast_chat(&players, player_id, text).await,
+                            None => {
+                                if let Err(e) = send_message(&players, player_id, &invalid_chat()).await {
+                                    error!("Error sending error to player {}: {}", player_id, e);
+                                }
+                            }
+                        },
+                        Ok(ClientMessage::DeclinePromotion) => {
+                            if spectating {
+                                if let Some(connection) = players.lock().await.get_mut(&player_id) {
+                                    connection.declines_promotion = true;
+                                }
+                            } else {
+                                let response = ServerMessage::error("not_spectating", "Only spectators can be promoted");
+                                if let Err(e) = send_message(&players, player_id, &response).await {
+                                    error!("Error sending error to player {}: {}", player_id, e);
+                                }
+                            }
+                        }
+                        Ok(ClientMessage::SpectatorChat { text }) => {
+                            let text = chat_text(&text);
+                            let response = match text {
+                                Some(text) if spectating => {
+                                    broadcast_spectator_chat(&players, player_id, text).await;
+                                    continue;
+                                }
+                                Some(_) => ServerMessage::error("not_spectating", "Only spectators can use spectator chat"),
+                                None => invalid_chat(),
+                            };
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending error to player {}: {}", player_id, e);
+                            }
+                        }
+                        Ok(ClientMessage::Spectate { code, password }) => {
+                            match rooms.spectate(&room, player_id, code.as_deref(), password.as_deref()).await {
+                                Ok(watched) => {
+                                    let left = std::mem::replace(&mut room, watched);
+                                    players = room.players.clone();
+                                    game_state = room.game_state.clone();
+                                    
+                                    if !Arc::ptr_eq(&left, &room) {
+                                        send_room_views(&left).await;
+                                    }
+                                    let view = view_for(&*game_state.lock().await, player_id, true);
+                                    if let Err(e) = send_game_state_to_player(&players, player_id, &view).await {
+                                        error!("Error sending game state to player {}: {}", player_id, e);
+                                    }
+                                }
+                                Err(RoomError::SpectatorsFull) => {
+                                    if let Err(e) = send_message(&players, player_id, &ServerMessage::SpectatorsFull).await {
+                                        error!("Error sending spectators full notice to player {}: {}", player_id, e);
+                                    }
+                                }
+                                Err(e) => {
+                                    let response = ServerMessage::error(e.code(), e.to_string());
+                                    if let Err(e) = send_message(&players, player_id, &response).await {
+                                        error!("Error sending error to player {}: {}", player_id, e);
+                                    }
+                                }
+                            }
+                        }
+                        Ok(ClientMessage::Hello { client_version }) => {
+                            debug!("Player {} is running client version {}", player_id, client_version);
+                            if let Some(min_version) = &config.min_client_version {
+                                if version_below(&client_version, min_version) {
+                                    info!("Player {} has outdated client {}, disconnecting", player_id, client_version);
+                                    
+                                    let response = ServerMessage::UpgradeRequired {
+                                        min_version: min_version.clone(),
+                                        download_url: config.client_download_url.clone(),
+                                    };
+                                    if let Err(e) = send_message(&players, player_id, &response).await {
+                                        error!("Error sending upgrade notice to player {}: {}", player_id, e);
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+                        Ok(ClientMessage::JoinSeat { seat }) => {
+                            let accepted = game_state.lock().await.request_seat(player_id, seat);
+                            
+                            let response = if accepted {
+                                info!("Player {} moved to seat {}", player_id, seat);
+                                ServerMessage::SeatAssigned { seat }
+                            } else {
+                                ServerMessage::error("seat_unavailable", format!("Seat {} is not available", seat))
+                            };
+                            
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending seat response to player {}: {}", player_id, e);
+                            }
+                        }
+                        Ok(ClientMessage::Ping { nonce }) => {
+                            // Answer straight away without touching the game state
+                            let response = ServerMessage::Pong {
+                                nonce,
+                                server_time: unix_time_millis(),
+                            };
+                            
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending pong to player {}: {}", player_id, e);
+                            }
+                        }
+                        Ok(ClientMessage::SetViewOptions { full_center_piles, center_pile_depth, compact_views }) => {
+                            if let Some(connection) = players.lock().await.get_mut(&player_id) {
+                                // A depth of zero would hide the tops players need, so it counts as one
+                                connection.center_pile_depth = if full_center_piles {
+                                    None
+                                } else {
+                                    Some(center_pile_depth.unwrap_or(1).max(1))
+                                };
+                                connection.compact_views = compact_views;
+                            }
+                            
+                            // Resend the current state so the new options take effect straight away
+                            let view = view_for(&*game_state.lock().await, player_id, spectating);
+                            if let Err(e) = send_game_state_to_player(&players, player_id, &view).await {
+                                error!("Error sending game state to player {}: {}", player_id, e);
+                            }
+                        }
+                        Ok(ClientMessage::SetPileSelection { mode }) => {
+                            pile_selection = mode;
+                        }
+                        Ok(ClientMessage::GetStatus) => {
+                            let status = game_state.lock().await.player_status(player_id);
+                            let response = match status {
+                                Some(status) => ServerMessage::Status(status),
+                                None => ServerMessage::error(
+                                    ActionError::PlayerNotFound.code(),
+                                    ActionError::PlayerNotFound.to_string(),
+                                ),
+                            };
+                            
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending status to player {}: {}", player_id, e);
+                            }
+                        }
+                        Ok(ClientMessage::Active) => {
+                            // Heartbeat only; the activity notice was handled above
+                        }
+                        Ok(ClientMessage::Action(action)) => {
+                            apply_action(&players, &game_state, &config, player_id, action, pile_selection).await;
+                        }
+                        Ok(ClientMessage::KeyedAction { action, idempotency_key }) => {
+                            // A retry of an action that was already applied gets the original ack again
+                            if let Some(result) = rooms.recent_action(player_id, &idempotency_key).await {
+                                debug!("Replaying ack for duplicate action {:?} from player {}", idempotency_key, player_id);
+                                if let Err(e) = send_message(&players, player_id, &ServerMessage::Ack(result)).await {
+                                    error!("Error sending ack to player {}: {}", player_id, e);
+                                }
+                                continue;
+                            }
+                            
+                            if let Some(result) = apply_action(&players, &game_state, &config, player_id, action, pile_selection).await {
+                                rooms.remember_action(player_id, idempotency_key, result).await;
+                            }
+                        }
+                        Err(DecodeError::Unknown(name)) => {
+                            // Tell the client rather than dropping the message silently
+                            warn!("Received unknown action {:?} from player {}", name, player_id);
+                            
+                            let response = ServerMessage::error("unknown_action", format!("Unknown action {:?}", name));
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending error to player {}: {}", player_id, e);
+                            }
+                            violations += 1;
+                        }
+                        Err(DecodeError::Invalid { name, error }) => {
+                            warn!("Received invalid {} from player {}: {}", name, player_id, error);
+                            
+                            let response = ServerMessage::error("invalid_action", format!("Invalid {}: {}", name, error));
+                            if let Err(e) = send_message(&players, player_id, &response).await {
+                                error!("Error sending error to player {}: {}", player_id, e);
+                            }
+                            violations += 1;
+                        }
+                        Err(DecodeError::Malformed(e)) => {
+                            warn!("Received invalid message from player {}: {}", player_id, e);
+                            violations += 1;
+                        }
+                    }
+                    
+                    // Drop clients that keep sending garbage, optionally forfeiting their game
+                    if config.max_protocol_violations.is_some_and(|limit| violations > limit) {
+                        warn!("Player {} exceeded the protocol violation limit, disconnecting", player_id);
+                        
+                        let response = ServerMessage::error("too_many_violations", "Too many invalid messages");
+                        if let Err(e) = send_message(&players, player_id, &response).await {
+                            error!("Error sending error to player {}: {}", player_id, e);
+                        }
+                        if config.forfeit_on_violations {
+                            forfeit_game(&players, &game_state, &config, player_id, false).await;
+                        }
+                        break;
+                    }
+                } else {
+                    // Fragmented messages arrive here already reassembled, so only control frames are left
+                    match msg {
+                        Message::Ping(_) | Message::Pong(_) => {
+                            // Pings are answered by the WebSocket library
+                        }
+                        Message::Close(frame) => {
+                            debug!("Player {} sent close frame: {:?}", player_id, frame);
+                        }
+                        other => {
+                            warn!("Ignoring unexpected frame from player {}: {:?}", player_id, other);
                         }
-                    } else {
-                        warn!("Received invalid message format");
                     }
                 }
             }
@@ -171,35 +1576,60 @@ async fn handle_connection(
         players_lock.remove(&player_id);
         info!("Player {} disconnected", player_id);
         
-        // Reset game if a player disconnects
-        if !players_lock.is_empty() {
-            let mut game = game_state.lock().await;
-            *game = GameState::new();
-            
-            // Add remaining players to the new game state
-            for &id in players_lock.keys() {
-                game.add_player(id);
-            }
-            
+        metrics.record_disconnect(connected_at.elapsed());
+        debug!("Connection metrics: {:?}", metrics.snapshot());
+        
+        // Pause the game while the seat is empty. A player who doesn't reconnect within the
+        // grace period forfeits, and the game is reset for whoever is left once they've seen the
+        // result. A game that hasn't started or is over has nothing to hold the seat for, so it's
+        // reset straight away.
+        let mut game = game_state.lock().await;
+        let seated = game.players.iter().any(|p| p.id == player_id);
+        if seated && !matches!(game.phase(), GamePhase::Playing | GamePhase::Paused) {
             info!("Game reset due to player disconnect");
+            replace_game_keeping_seats(&players, &game_state, &config, players_lock, game).await;
+            rooms.forget_session(player_id).await;
+        } else if seated {
+            if let Err(e) = game.transition_to(GamePhase::Paused) {
+                debug!("Not pausing: {}", e);
+            }
             
-            // Create player views
-            let player_views: Vec<_> = players_lock.keys().map(|&id| {
-                (id, game.create_player_view(id))
-            }).collect();
+            let player_views: Vec<_> = table_views(&players_lock, &game);
             
             // Drop locks before async operations
             drop(game);
             drop(players_lock);
             
-            // Notify remaining players
-            for (id, view) in player_views {
-                if let Err(e) = send_game_state_to_player(&players, id, &view).await {
-                    error!("Error sending game state to player {}: {}", id, e);
+            send_player_views(&players, player_views).await;
+            
+            let rooms = rooms.clone();
+            let room = room.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(config.disconnect_grace).await;
+                // A spectator may carry on in the player's place instead of the game being forfeited
+                let continue_game = config.promote_spectators && config.spectator_handoff == SpectatorHandoff::ContinueGame;
+                if continue_game && hand_seat_to_spectator(&room, &config, player_id).await {
+                    rooms.forget_session(player_id).await;
+                    return;
                 }
-            }
+                // Whoever was left gets a round break to see the result before the seat reopens
+                if forfeit_game(&room.players, &room.game_state, &config, player_id, true).await {
+                    tokio::time::sleep(config.round_break).await;
+                }
+                reset_game_after_disconnect(&room.players, &room.game_state, &config, player_id).await;
+                
+                // The session is only worth keeping while the player still has a seat
+                if !room.game_state.lock().await.players.iter().any(|p| p.id == player_id) {
+                    rooms.forget_session(player_id).await;
+                }
+                rooms.remove_if_empty(&room).await;
+            });
+        } else {
+            rooms.forget_session(player_id).await;
         }
     }
+    rooms.remove_if_empty(&room).await;
     
     Ok(())
 }
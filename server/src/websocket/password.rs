@@ -0,0 +1,27 @@
+use sha2::{Digest, Sha256};
+
+// Compare a given password with the expected one in constant time. Both are hashed first so
+// the comparison doesn't leak the expected password's length either.
+pub fn matches(expected: &str, given: &str) -> bool {
+    let expected = Sha256::digest(expected.as_bytes());
+    let given = Sha256::digest(given.as_bytes());
+    
+    expected
+        .iter()
+        .zip(given.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn passwords_must_match_exactly() {
+        assert!(matches("hunter2", "hunter2"));
+        assert!(!matches("hunter2", "hunter"));
+        assert!(!matches("hunter2", "Hunter2"));
+        assert!(!matches("hunter2", ""));
+    }
+}
@@ -0,0 +1,19 @@
+use std::fmt;
+use uuid::Uuid;
+
+use crate::game::PlayerView;
+
+// Hook that adjusts each player's view just before it is sent, e.g. to add computed hints in
+// `extras`, redact fields, or render a variant differently. Views are projected before the
+// connection's own options, such as the center pile depth, are applied.
+pub trait ViewProjector: fmt::Debug + Send + Sync {
+    fn project(&self, player_id: Uuid, view: &mut PlayerView) {
+        let _ = (player_id, view);
+    }
+}
+
+// Sends views unchanged
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityProjector;
+
+impl ViewProjector for IdentityProjector {}
@@ -0,0 +1,562 @@
+use log::{debug, info};
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::password;
+use super::{Connection, Players, ServerConfig, SharedGameState, SpectatorAccess};
+use crate::cache::LruCache;
+use crate::game::{ActionResult, GameConfig, GamePhase, GameState};
+
+// Characters room codes are made of, leaving out ones easily mistaken for each other
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 5;
+// Longest match a private room may be created for
+const MAX_BEST_OF: u32 = 9;
+// Most session tokens remembered at once; the least recently used are forgotten beyond that
+const MAX_SESSIONS: usize = 4096;
+
+// One independent game and the connections playing it
+pub(super) struct Room {
+    pub(super) id: u64,
+    // Code friends join a private room with; players are only matched into rooms without one
+    pub(super) code: Option<String>,
+    // Password needed to join or watch a private room by its code, if its creator set one
+    password: Option<String>,
+    // What the room's spectators are shown of its game
+    spectator_access: SpectatorAccess,
+    pub(super) players: Players,
+    pub(super) game_state: SharedGameState,
+}
+
+impl Room {
+    // Take a connection in to watch the game, shown as much of it as the room allows
+    async fn admit_spectator(&self, player_id: Uuid, mut connection: Connection) {
+        connection.spectating = true;
+        connection.end_only = self.spectator_access == SpectatorAccess::EndOnly;
+        connection.watching_since = tokio::time::Instant::now();
+        connection.declines_promotion = false;
+        self.players.lock().await.insert(player_id, connection);
+        info!("Player {} is watching room {}", player_id, self.id);
+    }
+    
+    // Whether the game can take another spectator, not counting this one if they're already watching
+    async fn has_space_for_spectator(&self, max_spectators: Option<usize>, watcher: Uuid) -> bool {
+        let max = match max_spectators {
+            Some(max) => max,
+            None => return true,
+        };
+        let players_lock = self.players.lock().await;
+        players_lock.iter().filter(|(&id, connection)| connection.spectating && id != watcher).count() < max
+    }
+    
+    // Refuse a password that doesn't match the room's, compared in constant time; rooms without
+    // one are open to anyone
+    fn check_password(&self, given: Option<&str>) -> Result<(), RoomError> {
+        match &self.password {
+            Some(expected) if !password::matches(expected, given.unwrap_or_default()) => Err(RoomError::WrongPassword),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Where a player asked to be moved
+pub(super) enum Destination {
+    // A new private room with a fresh code, playing matches of this many games or up to this
+    // many points if given, only open to those with the password if it has one, and showing its
+    // spectators as much as asked
+    NewPrivateRoom {
+        best_of: Option<u32>,
+        point_target: Option<u32>,
+        password: Option<String>,
+        spectators: Option<SpectatorAccess>,
+    },
+    // The private room with this code, with the password given for it
+    Code { code: String, password: Option<String> },
+    // A public room with someone waiting to be matched, or a new one to wait in
+    Matchmaking,
+}
+
+// Why a player couldn't be moved to another room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RoomError {
+    NotFound,
+    WrongPassword,
+    Full,
+    SameClient,
+    AlreadyPlaying,
+    NoRoomsLeft,
+    SessionNotFound,
+    SessionInUse,
+    NotInProgress,
+    SpectatorsFull,
+}
+
+impl RoomError {
+    // Machine-readable code for reporting the error to clients
+    pub(super) fn code(&self) -> &'static str {
+        match self {
+            RoomError::NotFound => "room_not_found",
+            RoomError::WrongPassword => "wrong_password",
+            RoomError::Full => "room_full",
+            RoomError::SameClient => "same_client",
+            RoomError::AlreadyPlaying => "already_playing",
+            RoomError::NoRoomsLeft => "no_rooms_left",
+            RoomError::SessionNotFound => "session_not_found",
+            RoomError::SessionInUse => "session_in_use",
+            RoomError::NotInProgress => "no_game_in_progress",
+            RoomError::SpectatorsFull => "spectators_full",
+        }
+    }
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            RoomError::NotFound => "there is no room with that code",
+            RoomError::WrongPassword => "wrong or missing password for that room",
+            RoomError::Full => "that room's game already has both players",
+            RoomError::SameClient => "players in that room are connecting from the same address as you",
+            RoomError::AlreadyPlaying => "rooms can only be changed before the game starts",
+            RoomError::NoRoomsLeft => "every room is taken; try again later",
+            RoomError::SessionNotFound => "that session has expired or never existed",
+            RoomError::SessionInUse => "that session is still connected",
+            RoomError::NotInProgress => "there is no game in progress to watch",
+            RoomError::SpectatorsFull => "that game already has as many spectators as it allows",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+// Every open room on the server. Rooms are opened as players arrive and dropped once the last
+// of their players leaves.
+pub(super) struct Rooms {
+    // Rules every new room's game is created with
+    config: GameConfig,
+    // Most rooms open at once; unlimited if unset
+    max_rooms: Option<usize>,
+    // Whether players are kept from being matched with someone connecting from the same address
+    require_distinct_clients: bool,
+    // What spectators of rooms that didn't choose are shown
+    spectator_access: SpectatorAccess,
+    // Most spectators watching each room's game; unlimited if unset
+    max_spectators: Option<usize>,
+    rooms: Mutex<Vec<Arc<Room>>>,
+    next_id: AtomicU64,
+    // Player each session token was issued to, for picking a game back up after reconnecting
+    sessions: Mutex<LruCache<String, Uuid>>,
+    // Acks of each player's recent keyed actions by idempotency key, kept with the session so a
+    // retry after reconnecting isn't applied twice
+    recent_actions: Mutex<HashMap<Uuid, LruCache<String, ActionResult>>>,
+    idempotency_keys: usize,
+    idempotency_ttl: Option<Duration>,
+}
+
+impl Rooms {
+    pub(super) fn new(config: GameConfig, server_config: &ServerConfig) -> Self {
+        Rooms {
+            config,
+            max_rooms: server_config.max_rooms,
+            require_distinct_clients: server_config.require_distinct_clients,
+            spectator_access: server_config.spectator_access,
+            max_spectators: server_config.max_spectators_per_room,
+            rooms: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            sessions: Mutex::new(LruCache::new(MAX_SESSIONS, server_config.session_ttl)),
+            recent_actions: Mutex::new(HashMap::new()),
+            idempotency_keys: server_config.idempotency_keys,
+            idempotency_ttl: server_config.idempotency_ttl,
+        }
+    }
+    
+    // The rooms open right now, for tasks that visit each in turn
+    pub(super) async fn all(&self) -> Vec<Arc<Room>> {
+        self.rooms.lock().await.clone()
+    }
+    
+    // Seat an arriving player and add their connection to the room. Matched players go to the
+    // first public room waiting for an opponent, or a new one; the others get a private room of
+    // their own. If every room is taken and no more may be opened, the player spectates a public
+    // game in progress instead, failing if there isn't one with space for them either.
+    pub(super) async fn join(&self, player_id: Uuid, connection: Connection, matched: bool) -> Result<Arc<Room>, RoomError> {
+        // Held throughout so two arriving players can't both claim the last open seat
+        let mut rooms = self.rooms.lock().await;
+        
+        let waiting = if matched {
+            self.waiting_public_room(&rooms, None, connection.client_ip).await
+        } else {
+            None
+        };
+        let room = match waiting {
+            Some(room) => room,
+            None => {
+                if self.max_rooms.is_some_and(|max| rooms.len() >= max) {
+                    // Rather than turn the player away, let them watch a game in progress
+                    let room = match self.game_to_watch(&rooms, None, None, player_id).await {
+                        Ok(room) => room,
+                        Err(RoomError::SpectatorsFull) => return Err(RoomError::SpectatorsFull),
+                        Err(_) => return Err(RoomError::NoRoomsLeft),
+                    };
+                    room.admit_spectator(player_id, connection).await;
+                    return Ok(room);
+                }
+                let code = if matched { None } else { Some(unused_code(&rooms)) };
+                let room = self.open_room(code, None, None, None, None);
+                rooms.push(room.clone());
+                room
+            }
+        };
+        
+        let mut players_lock = room.players.lock().await;
+        room.game_state.lock().await.add_player(player_id);
+        players_lock.insert(player_id, connection);
+        info!("Player {} joined room {}", player_id, room.id);
+        drop(players_lock);
+        
+        Ok(room)
+    }
+    
+    // Move a player who hasn't started playing to another room, leaving the game they were
+    // waiting in. A failed move leaves them where they were.
+    pub(super) async fn move_player(
+        &self,
+        from: &Arc<Room>,
+        player_id: Uuid,
+        destination: Destination,
+    ) -> Result<Arc<Room>, RoomError> {
+        let mut rooms = self.rooms.lock().await;
+        
+        // Check the destination first so the player doesn't give up their seat for nothing
+        let target = match &destination {
+            Destination::NewPrivateRoom { .. } => None,
+            Destination::Code { code, password } => {
+                let room = rooms
+                    .iter()
+                    .find(|room| room.code.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(code.trim())))
+                    .ok_or(RoomError::NotFound)?;
+                room.check_password(password.as_deref())?;
+                if Arc::ptr_eq(room, from) {
+                    return Ok(room.clone());
+                }
+                let client_ip = from.players.lock().await.get(&player_id).and_then(|c| c.client_ip);
+                let players_lock = room.players.lock().await;
+                let game = room.game_state.lock().await;
+                if game.phase() != GamePhase::Waiting {
+                    return Err(RoomError::Full);
+                }
+                // Friends are kept apart like strangers, so a code can't be used to play oneself
+                if self.require_distinct_clients && shares_client(&players_lock, &game, client_ip) {
+                    return Err(RoomError::SameClient);
+                }
+                Some(room.clone())
+            }
+            Destination::Matchmaking => {
+                let client_ip = from.players.lock().await.get(&player_id).and_then(|c| c.client_ip);
+                self.waiting_public_room(&rooms, Some(from), client_ip).await
+            }
+        };
+        
+        // A new room is needed; the room being left closes if the player was alone in it
+        if target.is_none() {
+            let alone = from.players.lock().await.len() <= 1;
+            if self.max_rooms.is_some_and(|max| rooms.len() - usize::from(alone) >= max) {
+                return Err(RoomError::NoRoomsLeft);
+            }
+        }
+        
+        let mut connection = leave(&mut rooms, from, player_id).await?;
+        connection.spectating = false;
+        connection.end_only = false;
+        
+        let room = match target {
+            Some(room) => room,
+            None => {
+                let room = match destination {
+                    Destination::NewPrivateRoom { best_of, point_target, password, spectators } => {
+                        self.open_room(Some(unused_code(&rooms)), password, best_of, point_target, spectators)
+                    }
+                    _ => self.open_room(None, None, None, None, None),
+                };
+                rooms.push(room.clone());
+                room
+            }
+        };
+        let mut players_lock = room.players.lock().await;
+        let mut game = room.game_state.lock().await;
+        game.add_player(player_id);
+        game.set_player_name(player_id, connection.name.clone());
+        drop(game);
+        players_lock.insert(player_id, connection);
+        info!("Player {} moved to room {}", player_id, room.id);
+        drop(players_lock);
+        
+        Ok(room)
+    }
+    
+    // Issue a token the player can reconnect with after losing their connection
+    pub(super) async fn open_session(&self, player_id: Uuid) -> String {
+        let token = Uuid::new_v4().simple().to_string();
+        self.sessions.lock().await.insert(token.clone(), player_id);
+        token
+    }
+    
+    // Invalidate a player's session once there is no game left for them to come back to
+    pub(super) async fn forget_session(&self, player_id: Uuid) {
+        self.revoke_tokens(player_id).await;
+        self.recent_actions.lock().await.remove(&player_id);
+    }
+    
+    // The ack of a keyed action the player already took, if it's still remembered
+    pub(super) async fn recent_action(&self, player_id: Uuid, idempotency_key: &str) -> Option<ActionResult> {
+        self.recent_actions.lock().await.get_mut(&player_id)?.get(idempotency_key).copied()
+    }
+    
+    // Remember the ack of a keyed action, for answering retries of it
+    pub(super) async fn remember_action(&self, player_id: Uuid, idempotency_key: String, result: ActionResult) {
+        self.recent_actions
+            .lock()
+            .await
+            .entry(player_id)
+            .or_insert_with(|| LruCache::new(self.idempotency_keys, self.idempotency_ttl))
+            .insert(idempotency_key, result);
+    }
+    
+    async fn revoke_tokens(&self, player_id: Uuid) {
+        self.sessions.lock().await.retain(|_, &id| id != player_id);
+    }
+    
+    // Hand a disconnected player's seat to a new connection presenting their session token.
+    // The connection leaves the room it was put in on arrival, where it must not be playing, and
+    // takes over the player's id; their game resumes once every seat is connected
+    // again. The token is used up, so returns the room, the player's id and a fresh token.
+    pub(super) async fn reconnect(
+        &self,
+        from: &Arc<Room>,
+        connection_id: Uuid,
+        token: &str,
+    ) -> Result<(Arc<Room>, Uuid, String), RoomError> {
+        let mut rooms = self.rooms.lock().await;
+        
+        let player_id = *self.sessions.lock().await.get(token).ok_or(RoomError::SessionNotFound)?;
+        let mut target = None;
+        for room in rooms.iter() {
+            let players_lock = room.players.lock().await;
+            let game = room.game_state.lock().await;
+            if !game.players.iter().any(|p| p.id == player_id) {
+                continue;
+            }
+            if players_lock.contains_key(&player_id) {
+                return Err(RoomError::SessionInUse);
+            }
+            target = Some(room.clone());
+            break;
+        }
+        let room = target.ok_or(RoomError::SessionNotFound)?;
+        
+        let mut connection = leave(&mut rooms, from, connection_id).await?;
+        connection.spectating = false;
+        connection.end_only = false;
+        self.forget_session(connection_id).await;
+        self.revoke_tokens(player_id).await;
+        let token = self.open_session(player_id).await;
+        
+        let mut players_lock = room.players.lock().await;
+        let mut game = room.game_state.lock().await;
+        // A name chosen before reconnecting replaces the one the seat had
+        if connection.name.is_some() {
+            game.set_player_name(player_id, connection.name.clone());
+        }
+        players_lock.insert(player_id, connection);
+        let all_connected = game.players.iter().all(|p| players_lock.contains_key(&p.id));
+        if game.phase() == GamePhase::Paused && all_connected {
+            if let Err(e) = game.transition_to(GamePhase::Playing) {
+                debug!("Not resuming room {}: {}", room.id, e);
+            }
+        }
+        info!("Player {} reconnected to room {}", player_id, room.id);
+        drop(game);
+        drop(players_lock);
+        
+        Ok((room, player_id, token))
+    }
+    
+    // Move a connection that isn't playing to watch a game in progress: the private room with
+    // this code, or else the first public room playing. Spectators get their own view of the
+    // table and can't take part; a later move or reconnect seats them again.
+    pub(super) async fn spectate(
+        &self,
+        from: &Arc<Room>,
+        player_id: Uuid,
+        code: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<Arc<Room>, RoomError> {
+        let mut rooms = self.rooms.lock().await;
+        
+        let room = self.game_to_watch(&rooms, code, password, player_id).await?;
+        if Arc::ptr_eq(&room, from) {
+            // Already at this table, either watching it or playing in it
+            let seated = room.game_state.lock().await.players.iter().any(|p| p.id == player_id);
+            return if seated { Err(RoomError::AlreadyPlaying) } else { Ok(room) };
+        }
+        let connection = leave(&mut rooms, from, player_id).await?;
+        room.admit_spectator(player_id, connection).await;
+        
+        Ok(room)
+    }
+    
+    // Close a room once nobody is connected to it, unless its game is paused waiting for a
+    // player to reconnect
+    pub(super) async fn remove_if_empty(&self, room: &Arc<Room>) {
+        let mut rooms = self.rooms.lock().await;
+        if !room.players.lock().await.is_empty() || room.game_state.lock().await.phase() == GamePhase::Paused {
+            return;
+        }
+        
+        rooms.retain(|open| !Arc::ptr_eq(open, room));
+        info!("Closed room {}", room.id);
+    }
+    
+    // The first public room other than `except` whose game is waiting for an opponent this
+    // player may be matched with
+    async fn waiting_public_room(
+        &self,
+        rooms: &[Arc<Room>],
+        except: Option<&Arc<Room>>,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Arc<Room>> {
+        for room in rooms.iter().filter(|room| room.code.is_none()) {
+            if except.is_some_and(|except| Arc::ptr_eq(room, except)) {
+                continue;
+            }
+            
+            let players_lock = room.players.lock().await;
+            let game = room.game_state.lock().await;
+            if game.phase() != GamePhase::Waiting {
+                continue;
+            }
+            if self.require_distinct_clients && shares_client(&players_lock, &game, client_ip) {
+                continue;
+            }
+            return Some(room.clone());
+        }
+        None
+    }
+    
+    // The room with a game in progress to watch that has space for the watcher: the one with
+    // this code, given its password, or else the first public one
+    async fn game_to_watch(
+        &self,
+        rooms: &[Arc<Room>],
+        code: Option<&str>,
+        password: Option<&str>,
+        watcher: Uuid,
+    ) -> Result<Arc<Room>, RoomError> {
+        let mut spectators_full = false;
+        for room in rooms {
+            let matches = match code {
+                Some(code) => room.code.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(code.trim())),
+                None => room.code.is_none(),
+            };
+            if !matches {
+                continue;
+            }
+            if code.is_some() {
+                room.check_password(password)?;
+            }
+            
+            let in_progress = matches!(room.game_state.lock().await.phase(), GamePhase::Playing | GamePhase::Paused);
+            if in_progress && room.has_space_for_spectator(self.max_spectators, watcher).await {
+                return Ok(room.clone());
+            }
+            if code.is_some() {
+                return Err(if in_progress { RoomError::SpectatorsFull } else { RoomError::NotInProgress });
+            }
+            spectators_full |= in_progress;
+        }
+        Err(match code {
+            Some(_) => RoomError::NotFound,
+            None if spectators_full => RoomError::SpectatorsFull,
+            None => RoomError::NotInProgress,
+        })
+    }
+    
+    // A new room playing by the server's rules, apart from the match length and what spectators
+    // are shown if given
+    fn open_room(
+        &self,
+        code: Option<String>,
+        password: Option<String>,
+        best_of: Option<u32>,
+        point_target: Option<u32>,
+        spectators: Option<SpectatorAccess>,
+    ) -> Arc<Room> {
+        let mut config = self.config.clone();
+        if let Some(best_of) = best_of {
+            config.best_of = best_of.clamp(1, MAX_BEST_OF);
+        }
+        if point_target.is_some() {
+            config.point_target = point_target.filter(|&target| target > 0);
+        }
+        
+        Arc::new(Room {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            code,
+            password: password.filter(|password| !password.is_empty()),
+            spectator_access: spectators.unwrap_or(self.spectator_access),
+            players: Arc::new(Mutex::new(HashMap::new())),
+            game_state: Arc::new(Mutex::new(GameState::new(config))),
+        })
+    }
+}
+
+// Take a connection out of the room it's leaving, giving up its seat, and close the room if
+// nobody is left in it. Players can't leave a game they're playing.
+async fn leave(rooms: &mut Vec<Arc<Room>>, from: &Arc<Room>, player_id: Uuid) -> Result<Connection, RoomError> {
+    let connection = {
+        let mut players_lock = from.players.lock().await;
+        let mut game = from.game_state.lock().await;
+        let seated = game.players.iter().any(|p| p.id == player_id);
+        if seated && game.phase().has_started() {
+            return Err(RoomError::AlreadyPlaying);
+        }
+        game.remove_player(player_id);
+        players_lock.remove(&player_id).ok_or(RoomError::NotFound)?
+    };
+    if from.players.lock().await.is_empty() {
+        rooms.retain(|open| !Arc::ptr_eq(open, from));
+        info!("Closed room {}", from.id);
+    }
+    
+    Ok(connection)
+}
+
+// A random room code no open room is using
+fn unused_code(rooms: &[Arc<Room>]) -> String {
+    let mut rng = rand::thread_rng();
+    loop {
+        let code: String = (0..CODE_LENGTH)
+            .map(|_| *CODE_ALPHABET.choose(&mut rng).unwrap_or(&b'A') as char)
+            .collect();
+        if !rooms.iter().any(|room| room.code.as_deref() == Some(code.as_str())) {
+            return code;
+        }
+    }
+}
+
+// Whether a seated player in the room connected from this address
+fn shares_client(players: &HashMap<Uuid, Connection>, game: &GameState, client_ip: Option<IpAddr>) -> bool {
+    let client_ip = match client_ip {
+        Some(ip) => ip,
+        None => return false,
+    };
+    
+    game.players
+        .iter()
+        .any(|p| players.get(&p.id).and_then(|c| c.client_ip) == Some(client_ip))
+}
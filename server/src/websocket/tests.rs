@@ -0,0 +1,1670 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::DuplexStream;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{client_async, WebSocketStream};
+use uuid::Uuid;
+
+use super::messages::{ServerMessage, WireFormat, SERIALIZATION_FAILED_JSON};
+use super::rooms::{Room, Rooms};
+use super::{handle_connection, send_encoded, spawn_idle_resends, version_below, Connection, ConnectionMetrics, IdentityProjector, ServerConfig, SpectatorHandoff, ViewProjector};
+use crate::game::{Card, GameConfig, GameOutcome, GamePhase, PlayerView};
+
+// Client end of an in-memory connection to the handler
+type Client = WebSocketStream<DuplexStream>;
+
+// Longest a test waits for a message; the clock is paused, so this only fails tests that hang
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Rooms and settings shared by every connection a test opens, like the real server's
+struct TestServer {
+    rooms: Arc<Rooms>,
+    metrics: Arc<ConnectionMetrics>,
+    config: Arc<ServerConfig>,
+}
+
+impl TestServer {
+    fn new(config: ServerConfig) -> Self {
+        Self::with_game(config, GameConfig::default())
+    }
+    
+    fn with_game(config: ServerConfig, game_config: GameConfig) -> Self {
+        let rooms = Arc::new(Rooms::new(game_config, &config));
+        if let Some(interval) = config.idle_resend_interval {
+            spawn_idle_resends(rooms.clone(), interval);
+        }
+        TestServer {
+            rooms,
+            metrics: Arc::new(ConnectionMetrics::new()),
+            config: Arc::new(config),
+        }
+    }
+    
+    // Open a connection driven by `handle_connection` over an in-memory stream
+    async fn connect(&self) -> Client {
+        self.connect_from(None).await
+    }
+    
+    // Open a connection as if it came from this address
+    async fn connect_from(&self, client_ip: Option<IpAddr>) -> Client {
+        let mut ws = self.try_connect("ws://localhost/", client_ip).await.expect("handshake failed");
+        // Like the real client, say it's ready as soon as it's connected
+        if self.config.require_ready {
+            send(&mut ws, json!("Ready")).await;
+        }
+        ws
+    }
+    
+    // Open a connection to this URL, returning the handshake error if it's refused
+    async fn try_connect(&self, url: &str, client_ip: Option<IpAddr>) -> Result<Client, WsError> {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let rooms = self.rooms.clone();
+        let metrics = self.metrics.clone();
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let _ = handle_connection(server, client_ip, rooms, metrics, config).await;
+        });
+        let (ws, _) = client_async(url, client).await?;
+        Ok(ws)
+    }
+    
+    // Connect two players and wait until their game is dealt
+    async fn start_game(&self) -> [Client; 2] {
+        let mut a = self.connect().await;
+        let mut b = self.connect().await;
+        expect(&mut a, "GameState").await;
+        expect(&mut b, "GameState").await;
+        [a, b]
+    }
+    
+    // Like `start_game`, also returning each player's id and session token
+    async fn start_game_with_sessions(&self) -> [(Client, Uuid, String); 2] {
+        let mut a = self.connect().await;
+        let a_token = token(&mut a).await;
+        let mut b = self.connect().await;
+        let b_token = token(&mut b).await;
+        let a_id = dealt_player_id(&mut a).await;
+        let b_id = dealt_player_id(&mut b).await;
+        [(a, a_id, a_token), (b, b_id, b_token)]
+    }
+    
+    // The first room opened, where the first two players to connect are seated
+    async fn room(&self) -> Arc<Room> {
+        self.rooms.all().await.first().cloned().expect("no room is open")
+    }
+    
+    // The room whose game has this player seated
+    async fn room_of(&self, player_id: Uuid) -> Arc<Room> {
+        for room in self.rooms.all().await {
+            if room.game_state.lock().await.players.iter().any(|p| p.id == player_id) {
+                return room;
+            }
+        }
+        panic!("player {} isn't seated anywhere", player_id);
+    }
+    
+    async fn phase_of(&self, player_id: Uuid) -> GamePhase {
+        self.room_of(player_id).await.game_state.lock().await.phase()
+    }
+    
+    // The room with a game in play, for tests with a single game
+    async fn playing_room(&self) -> Arc<Room> {
+        for room in self.rooms.all().await {
+            if room.game_state.lock().await.phase() == GamePhase::Playing {
+                return room;
+            }
+        }
+        panic!("no game is in play");
+    }
+}
+
+async fn send(ws: &mut Client, message: Value) {
+    ws.send(Message::Text(message.to_string())).await.unwrap();
+}
+
+// The next text message from the server
+async fn next_message(ws: &mut Client) -> Value {
+    loop {
+        let frame = match tokio::time::timeout(RECEIVE_TIMEOUT, ws.next()).await {
+            Ok(Some(frame)) => frame.expect("receive failed"),
+            Ok(None) => panic!("connection closed while waiting for a message"),
+            Err(_) => panic!("no message arrived"),
+        };
+        if let Message::Text(text) = frame {
+            return serde_json::from_str(&text).expect("server sent invalid JSON");
+        }
+    }
+}
+
+// Read messages until one of this type arrives, skipping the others
+async fn expect(ws: &mut Client, message_type: &str) -> Value {
+    loop {
+        let message = next_message(ws).await;
+        if message["type"] == message_type {
+            return message;
+        }
+    }
+}
+
+// Check that nothing was sent since the last message read, by making sure the reply to a ping
+// is the very next message
+async fn expect_nothing_pending(ws: &mut Client) {
+    send(ws, json!({ "Ping": { "nonce": 0 } })).await;
+    let message = next_message(ws).await;
+    assert_eq!(message["type"], "Pong", "unexpected message {}", message);
+}
+
+async fn token(ws: &mut Client) -> String {
+    expect(ws, "Session").await["token"].as_str().unwrap().to_string()
+}
+
+// Wait for the game to be dealt and return the player's id from their first view of it
+async fn dealt_player_id(ws: &mut Client) -> Uuid {
+    expect(ws, "GameConfig").await;
+    let view = expect(ws, "GameState").await;
+    serde_json::from_value(view["player_id"].clone()).unwrap()
+}
+
+// Let every task run until it is waiting on a timer or a connection
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(1)).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn seat_requests_are_answered() {
+    let server = TestServer::with_game(ServerConfig::default(), GameConfig {
+        allow_seat_requests: true,
+        ..GameConfig::default()
+    });
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "JoinSeat": { "seat": 1 } })).await;
+    assert_eq!(expect(&mut a, "SeatAssigned").await["seat"], 1);
+    
+    // The second player takes the free seat and starts the game, after which seats are fixed
+    let mut b = server.connect().await;
+    expect(&mut b, "GameState").await;
+    send(&mut a, json!({ "JoinSeat": { "seat": 0 } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "seat_unavailable");
+}
+
+#[tokio::test(start_paused = true)]
+async fn ping_is_answered_with_its_nonce() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "Ping": { "nonce": 12345 } })).await;
+    
+    let pong = expect(&mut a, "Pong").await;
+    assert_eq!(pong["nonce"], 12345);
+    assert!(pong["server_time"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejected_actions_are_not_broadcast() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    
+    send(&mut a, json!({ "PlayCard": { "card_index": 99 } })).await;
+    assert_eq!(next_message(&mut a).await["code"], "invalid_card_index");
+    expect_nothing_pending(&mut a).await;
+    // The opponent only hears that the player is active
+    assert_eq!(next_message(&mut b).await["type"], "OpponentActive");
+    expect_nothing_pending(&mut b).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn unknown_actions_get_an_unknown_action_error() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    
+    send(&mut a, json!({ "Teleport": { "to": 3 } })).await;
+    let error = expect(&mut a, "Error").await;
+    assert_eq!(error["code"], "unknown_action");
+    assert!(error["message"].as_str().unwrap().contains("Teleport"));
+    
+    send(&mut a, json!("Dance")).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "unknown_action");
+}
+
+#[tokio::test(start_paused = true)]
+async fn malformed_actions_get_an_invalid_action_error() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    
+    send(&mut a, json!({ "PlayCard": { "pile_index": 0 } })).await;
+    let error = next_message(&mut a).await;
+    assert_eq!(error["code"], "invalid_action");
+    assert!(error["message"].as_str().unwrap().contains("card_index"));
+    
+    send(&mut a, json!({ "JoinRoom": { "code": 7 } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "invalid_action");
+}
+
+#[tokio::test(start_paused = true)]
+async fn ack_arrives_before_the_resulting_state() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    assert_eq!(next_message(&mut a).await["type"], "Ack");
+    assert_eq!(next_message(&mut a).await["type"], "GameState");
+}
+
+#[test]
+fn serialization_fallback_is_a_regular_error() {
+    let fallback: Value = serde_json::from_str(SERIALIZATION_FAILED_JSON).unwrap();
+    let error = ServerMessage::error("serialization_failed", fallback["message"].as_str().unwrap());
+    assert_eq!(fallback, serde_json::to_value(&error).unwrap());
+}
+
+#[tokio::test]
+async fn unserializable_messages_are_replaced_with_an_error() {
+    let (sender, mut queue) = tokio::sync::mpsc::channel(4);
+    let metrics = Arc::new(ConnectionMetrics::new());
+    let mut connection = Connection::new(sender, None, metrics, Arc::new(IdentityProjector));
+    
+    let result = send_encoded(&mut connection, Uuid::nil(), Err("unsupported value".to_string()));
+    assert!(result.is_err());
+    assert_eq!(queue.recv().await, Some(Message::Text(SERIALIZATION_FAILED_JSON.to_string())));
+    
+    // A client speaking MessagePack gets the error in MessagePack too
+    connection.format = WireFormat::MessagePack;
+    send_encoded(&mut connection, Uuid::nil(), Err("unsupported value".to_string())).unwrap_err();
+    let Some(Message::Binary(bytes)) = queue.recv().await else {
+        panic!("fallback wasn't sent as a binary frame");
+    };
+    let fallback: Value = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(fallback["code"], "serialization_failed");
+}
+
+#[tokio::test(start_paused = true)]
+async fn connection_lifecycle_over_an_in_memory_stream() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    assert_eq!(server.metrics.snapshot().active_connections, 2);
+    
+    // An action is acknowledged, then both players see the new state
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    expect(&mut a, "Ack").await;
+    expect(&mut a, "GameState").await;
+    expect(&mut b, "GameState").await;
+    
+    // Closing the connection counts as a disconnect and pauses the game for the opponent
+    a.close(None).await.unwrap();
+    let view = expect(&mut b, "GameState").await;
+    assert_eq!(view["paused"], true);
+    let metrics = server.metrics.snapshot();
+    assert_eq!((metrics.connects, metrics.disconnects), (2, 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn player_leaving_within_the_start_debounce_does_not_start_a_game() {
+    let config = ServerConfig::default();
+    let debounce = config.start_debounce;
+    let server = TestServer::new(config);
+    let mut a = server.connect().await;
+    let mut b = server.connect().await;
+    // Once b's ping is answered, b is seated and the start is pending
+    send(&mut b, json!({ "Ping": { "nonce": 0 } })).await;
+    expect(&mut b, "Pong").await;
+    
+    // Nothing has been dealt, so the seat is reopened without waiting out the grace period
+    drop(b);
+    tokio::time::sleep(debounce * 2).await;
+    {
+        let room = server.room().await;
+        let game = room.game_state.lock().await;
+        assert_eq!(game.phase(), GamePhase::Waiting);
+        assert_eq!(game.players.len(), 1);
+    }
+    
+    // The next opponent to stay starts the game as usual
+    let _c = server.connect().await;
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    assert_eq!(server.room().await.game_state.lock().await.phase(), GamePhase::Playing);
+}
+
+#[tokio::test(start_paused = true)]
+async fn activity_is_relayed_to_the_opponent_once_per_debounce_window() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        ..ServerConfig::default()
+    });
+    let [mut a, mut b] = server.start_game().await;
+    let mut spectator = server.connect().await;
+    expect(&mut spectator, "SpectatorState").await;
+    token(&mut spectator).await;
+    
+    send(&mut a, json!("Active")).await;
+    send(&mut a, json!("Active")).await;
+    let notice = expect(&mut b, "OpponentActive").await;
+    assert!(notice["at"].as_u64().unwrap() > 0);
+    
+    // The second heartbeat fell within the debounce window, nothing is echoed to the sender and
+    // spectators aren't anyone's opponent
+    expect_nothing_pending(&mut b).await;
+    expect_nothing_pending(&mut a).await;
+    expect_nothing_pending(&mut spectator).await;
+}
+
+// Leave each player a single playable card and have `a` play theirs, returning a's id and the
+// game over message that follows the final state
+async fn win_with_last_card(server: &TestServer, a: &mut Client) -> (Uuid, Value) {
+    {
+        let room = server.playing_room().await;
+        let mut game = room.game_state.lock().await;
+        let card: Card = "5H".parse().unwrap();
+        for player in game.players.iter_mut() {
+            player.hand = vec![card];
+            player.draw_pile.clear();
+        }
+        game.center_piles = vec![vec!["4S".parse().unwrap()], vec!["4C".parse().unwrap()]];
+    }
+    
+    send(a, json!({ "PlayCard": { "card_index": 0 } })).await;
+    assert_eq!(expect(a, "Ack").await["pile_index"], 0);
+    let view = next_message(a).await;
+    assert_eq!(view["type"], "GameState");
+    let a_id = serde_json::from_value(view["player_id"].clone()).unwrap();
+    let game_over = next_message(a).await;
+    assert_eq!(game_over["type"], "GameOver");
+    (a_id, game_over)
+}
+
+#[tokio::test(start_paused = true)]
+async fn game_over_follows_the_final_state() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    
+    let (a_id, game_over) = win_with_last_card(&server, &mut a).await;
+    assert_eq!(serde_json::from_value::<GameOutcome>(game_over["outcome"].clone()).unwrap(), GameOutcome::Win(a_id));
+    assert_eq!(expect(&mut b, "GameState").await["winner"], json!(a_id));
+    assert_eq!(next_message(&mut b).await["type"], "GameOver");
+}
+
+#[tokio::test(start_paused = true)]
+async fn full_server_offers_spectating_instead() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        ..ServerConfig::default()
+    });
+    let [_a, b] = server.start_game().await;
+    
+    let mut c = server.connect().await;
+    let notice = expect(&mut c, "RoomFull").await;
+    assert_eq!(notice["can_spectate"], true);
+    let view = expect(&mut c, "SpectatorState").await;
+    assert!(view["seats"].is_array());
+    assert!(view.get("player_id").is_none());
+    
+    // Asking to spectate the game already being watched is answered with its view
+    send(&mut c, json!({ "Spectate": { "code": null } })).await;
+    let view = expect(&mut c, "SpectatorState").await;
+    assert_eq!(view["game_started"], true);
+    
+    // The spectator follows the game, here as it's paused for a player who left
+    drop(b);
+    let view = expect(&mut c, "SpectatorState").await;
+    assert_eq!(view["paused"], true);
+}
+
+#[tokio::test(start_paused = true)]
+async fn full_server_with_nothing_to_watch_turns_players_away() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        match_on_connect: false,
+        ..ServerConfig::default()
+    });
+    let mut a = server.connect().await;
+    expect(&mut a, "RoomJoined").await;
+    
+    // The only room is a private one still waiting, so there's no game to offer instead
+    let mut b = server.try_connect("ws://localhost/", None).await.unwrap();
+    let notice = expect(&mut b, "RoomFull").await;
+    assert_eq!(notice["can_spectate"], false);
+    assert!(matches!(b.next().await, Some(Ok(Message::Close(_))) | Some(Err(_)) | None));
+}
+
+#[tokio::test(start_paused = true)]
+async fn spectators_past_the_cap_are_turned_away_until_one_leaves() {
+    let server = TestServer::new(ServerConfig {
+        max_spectators_per_room: Some(1),
+        ..ServerConfig::default()
+    });
+    let [_a, _b] = server.start_game().await;
+    let mut c = server.connect().await;
+    send(&mut c, json!({ "Spectate": { "code": null } })).await;
+    expect(&mut c, "SpectatorState").await;
+    
+    let mut d = server.connect().await;
+    send(&mut d, json!({ "Spectate": { "code": null } })).await;
+    expect(&mut d, "SpectatorsFull").await;
+    
+    drop(c);
+    settle().await;
+    send(&mut d, json!({ "Spectate": { "code": null } })).await;
+    expect(&mut d, "SpectatorState").await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn full_server_with_a_full_audience_turns_players_away() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        max_spectators_per_room: Some(1),
+        ..ServerConfig::default()
+    });
+    let [_a, _b] = server.start_game().await;
+    let mut c = server.connect().await;
+    expect(&mut c, "SpectatorState").await;
+    
+    let mut d = server.try_connect("ws://localhost/", None).await.unwrap();
+    expect(&mut d, "SpectatorsFull").await;
+    assert!(matches!(d.next().await, Some(Ok(Message::Close(_))) | Some(Err(_)) | None));
+}
+
+#[tokio::test(start_paused = true)]
+async fn lone_player_asking_for_state_gets_a_waiting_view() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "SetViewOptions": { "full_center_piles": false } })).await;
+    
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(view["game_started"], false);
+    assert_eq!(view["hand"], json!([]));
+    assert_eq!(view["opponent_hand_count"], 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn views_carry_pile_tops_unless_full_piles_are_asked_for() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    let pile: Vec<Card> = ["4S", "5S", "6S"].iter().map(|name| name.parse().unwrap()).collect();
+    server.room().await.game_state.lock().await.center_piles[0] = pile.clone();
+    let pile_sizes = |view: &Value| -> Vec<usize> {
+        view["center_piles"].as_array().unwrap().iter().map(|p| p.as_array().unwrap().len()).collect()
+    };
+    
+    send(&mut a, json!({ "SetViewOptions": { "full_center_piles": false } })).await;
+    let view = expect(&mut a, "GameState").await;
+    assert!(pile_sizes(&view).iter().all(|&size| size == 1));
+    assert_eq!(view["center_piles"][0][0], serde_json::to_value(pile[2]).unwrap());
+    
+    send(&mut a, json!({ "SetViewOptions": { "full_center_piles": true } })).await;
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(pile_sizes(&view)[0], 3);
+    assert_eq!(view["center_piles"][0], serde_json::to_value(&pile).unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn retried_keyed_action_is_applied_once() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    {
+        let room = server.room().await;
+        let mut game = room.game_state.lock().await;
+        game.center_piles = vec![vec!["4S".parse().unwrap()], vec!["KC".parse().unwrap()]];
+        for player in game.players.iter_mut() {
+            player.hand[0] = "5H".parse().unwrap();
+        }
+    }
+    
+    let play = json!({ "KeyedAction": {
+        "action": { "PlayCard": { "card_index": 0 } },
+        "idempotency_key": "play-1",
+    } });
+    send(&mut a, play.clone()).await;
+    let ack = expect(&mut a, "Ack").await;
+    assert_eq!(ack["pile_index"], 0);
+    expect(&mut a, "GameState").await;
+    
+    // The retry gets the same ack and changes nothing
+    send(&mut a, play).await;
+    assert_eq!(next_message(&mut a).await, ack);
+    expect_nothing_pending(&mut a).await;
+    assert_eq!(server.room().await.game_state.lock().await.center_piles[0].len(), 2);
+}
+
+// Two players connecting from the same address, returning how many rooms they were seated in
+async fn same_address_rooms(require_distinct_clients: bool) -> usize {
+    let server = TestServer::new(ServerConfig {
+        require_distinct_clients,
+        ..ServerConfig::default()
+    });
+    let address = Some(IpAddr::from([127, 0, 0, 1]));
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let mut ws = server.connect_from(address).await;
+        send(&mut ws, json!({ "Ping": { "nonce": 0 } })).await;
+        expect(&mut ws, "Pong").await;
+        clients.push(ws);
+    }
+    server.rooms.all().await.len()
+}
+
+#[tokio::test(start_paused = true)]
+async fn players_from_one_address_are_kept_apart_when_required() {
+    assert_eq!(same_address_rooms(true).await, 2);
+    assert_eq!(same_address_rooms(false).await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn private_room_codes_keep_players_from_one_address_apart_when_required() {
+    let server = TestServer::new(ServerConfig {
+        require_distinct_clients: true,
+        match_on_connect: false,
+        ..ServerConfig::default()
+    });
+    let address = Some(IpAddr::from([127, 0, 0, 1]));
+    let mut a = server.connect_from(address).await;
+    let code = expect(&mut a, "RoomJoined").await["code"].as_str().unwrap().to_string();
+    
+    let mut b = server.connect_from(address).await;
+    send(&mut b, json!({ "JoinRoom": { "code": code } })).await;
+    assert_eq!(expect(&mut b, "Error").await["code"], "same_client");
+    
+    let mut c = server.connect_from(Some(IpAddr::from([127, 0, 0, 2]))).await;
+    expect(&mut c, "RoomJoined").await;
+    send(&mut c, json!({ "JoinRoom": { "code": code } })).await;
+    assert_eq!(expect(&mut c, "RoomJoined").await["code"], json!(code));
+}
+
+// Read the next data frame, decoding MessagePack as well as JSON, and whether it was binary
+async fn next_frame(ws: &mut Client) -> (Value, bool) {
+    loop {
+        let frame = match tokio::time::timeout(RECEIVE_TIMEOUT, ws.next()).await {
+            Ok(Some(frame)) => frame.expect("receive failed"),
+            _ => panic!("no message arrived"),
+        };
+        match frame {
+            Message::Text(text) => return (serde_json::from_str(&text).expect("server sent invalid JSON"), false),
+            Message::Binary(bytes) => return (rmp_serde::from_slice(&bytes).expect("server sent invalid MessagePack"), true),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn each_message_is_answered_in_its_own_format() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    let binary = |message: Value| Message::Binary(rmp_serde::to_vec_named(&message).unwrap());
+    
+    a.send(binary(json!("RequestNewCenterCards"))).await.unwrap();
+    let (ack, is_binary) = next_frame(&mut a).await;
+    assert_eq!((ack["type"].as_str(), is_binary), (Some("Ack"), true));
+    let (view, is_binary) = next_frame(&mut a).await;
+    assert_eq!((view["type"].as_str(), is_binary), (Some("GameState"), true));
+    
+    send(&mut a, json!({ "Ping": { "nonce": 1 } })).await;
+    let (pong, is_binary) = next_frame(&mut a).await;
+    assert_eq!((pong["type"].as_str(), is_binary), (Some("Pong"), false));
+    assert_eq!(pong["nonce"], 1);
+    
+    a.send(binary(json!({ "Ping": { "nonce": 2 } }))).await.unwrap();
+    let (pong, is_binary) = next_frame(&mut a).await;
+    assert_eq!((pong["type"].as_str(), is_binary), (Some("Pong"), true));
+    assert_eq!(pong["nonce"], 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fragmented_message_is_reassembled_into_one_action() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    
+    let text = json!("RequestNewCenterCards").to_string();
+    let (head, tail) = text.as_bytes().split_at(8);
+    let first = Frame::message(head.to_vec(), OpCode::Data(Data::Text), false);
+    let rest = Frame::message(tail.to_vec(), OpCode::Data(Data::Continue), true);
+    a.send(Message::Frame(first)).await.unwrap();
+    a.send(Message::Frame(rest)).await.unwrap();
+    
+    assert_eq!(next_message(&mut a).await["type"], "Ack");
+    assert_eq!(next_message(&mut a).await["type"], "GameState");
+    expect_nothing_pending(&mut a).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn game_over_reveals_remaining_cards_when_enabled() {
+    for reveal_on_game_over in [false, true] {
+        let game_config = GameConfig {
+            reveal_on_game_over,
+            ..GameConfig::default()
+        };
+        let server = TestServer::with_game(ServerConfig::default(), game_config);
+        let [mut a, _b] = server.start_game().await;
+        
+        let (a_id, game_over) = win_with_last_card(&server, &mut a).await;
+        if !reveal_on_game_over {
+            assert!(game_over["revealed"].is_null());
+            continue;
+        }
+        
+        // The loser's last card is shown, and the winner has nothing left
+        for revealed in game_over["revealed"].as_array().unwrap() {
+            let expected: Vec<Card> = if revealed["player_id"] == json!(a_id) { vec![] } else { vec!["5H".parse().unwrap()] };
+            assert_eq!(revealed["hand"], serde_json::to_value(expected).unwrap());
+            assert_eq!(revealed["draw_pile"], json!([]));
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn players_are_told_when_their_game_cannot_be_dealt() {
+    let server = TestServer::with_game(
+        ServerConfig::default(),
+        GameConfig {
+            draw_pile_size: 30,
+            ..GameConfig::default()
+        },
+    );
+    let mut a = server.connect().await;
+    let mut b = server.connect().await;
+    
+    for ws in [&mut a, &mut b] {
+        assert_eq!(expect(ws, "Error").await["code"], "insufficient_deck");
+    }
+    assert!(!server.room().await.game_state.lock().await.phase().has_started());
+}
+
+// Start a game and have the first player send one more invalid message than the limit allows,
+// returning both connections and the second player's id
+async fn start_game_and_spam(forfeit_on_violations: bool) -> (TestServer, [Client; 2], Uuid) {
+    let server = TestServer::new(ServerConfig {
+        max_protocol_violations: Some(2),
+        forfeit_on_violations,
+        ..ServerConfig::default()
+    });
+    let mut a = server.connect().await;
+    let mut b = server.connect().await;
+    expect(&mut a, "GameState").await;
+    let b_id = serde_json::from_value(expect(&mut b, "GameState").await["player_id"].clone()).unwrap();
+    for _ in 0..3 {
+        a.send(Message::Text("not json".to_string())).await.unwrap();
+    }
+    assert_eq!(expect(&mut a, "Error").await["code"], "too_many_violations");
+    (server, [a, b], b_id)
+}
+
+#[tokio::test(start_paused = true)]
+async fn too_many_violations_forfeit_the_game_when_enabled() {
+    let (_server, [mut a, mut b], b_id) = start_game_and_spam(true).await;
+    for ws in [&mut a, &mut b] {
+        assert_eq!(expect(ws, "GameOver").await["outcome"], json!({ "Win": b_id }));
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn too_many_violations_only_disconnect_by_default() {
+    let (server, [_a, mut b], b_id) = start_game_and_spam(false).await;
+    let view = expect(&mut b, "GameState").await;
+    assert_eq!(view["paused"], true);
+    assert!(server.room().await.game_state.lock().await.players.iter().any(|p| p.id == b_id));
+}
+
+#[tokio::test(start_paused = true)]
+async fn status_reflects_a_pending_redeal_and_cooldowns() {
+    let server = TestServer::with_game(
+        ServerConfig::default(),
+        GameConfig {
+            max_redeals: 1,
+            clear_pile_cooldown: Some(2),
+            ..GameConfig::default()
+        },
+    );
+    let [mut a, mut b] = server.start_game().await;
+    
+    send(&mut b, json!("RequestRedeal")).await;
+    expect(&mut b, "Ack").await;
+    send(&mut a, json!({ "ClearPile": { "pile_index": 0 } })).await;
+    expect(&mut a, "Ack").await;
+    
+    send(&mut a, json!("GetStatus")).await;
+    let status = expect(&mut a, "Status").await;
+    assert_eq!(status["can_flip"], true);
+    assert_eq!(status["flip_rejection"], Value::Null);
+    assert_eq!(status["can_redeal"], true);
+    assert_eq!(status["opponent_requested_redeal"], true);
+    assert_eq!(status["cooldowns"]["clear_pile"], 2);
+    
+    send(&mut b, json!("GetStatus")).await;
+    let status = expect(&mut b, "Status").await;
+    assert_eq!(status["can_redeal"], false);
+    assert_eq!(status["opponent_requested_redeal"], false);
+}
+
+#[tokio::test(start_paused = true)]
+async fn disconnect_pauses_the_game_until_the_grace_period_ends_in_a_forfeit() {
+    let server = TestServer::new(ServerConfig::default());
+    let grace = server.config.disconnect_grace;
+    let [a, mut b] = server.start_game().await;
+    let hands: Vec<Vec<Card>> = server.room().await.game_state.lock().await.players.iter().map(|p| p.hand.clone()).collect();
+    
+    drop(a);
+    let view = expect(&mut b, "GameState").await;
+    assert_eq!(view["paused"], true);
+    send(&mut b, json!("RequestNewCenterCards")).await;
+    assert_eq!(expect(&mut b, "Error").await["code"], "action_not_allowed_in_phase");
+    
+    // The cards stay where they are for the grace period
+    tokio::time::sleep(grace / 2).await;
+    let room = server.room().await;
+    let game = room.game_state.lock().await;
+    assert_eq!(game.phase(), GamePhase::Paused);
+    assert_eq!(game.players.iter().map(|p| p.hand.clone()).collect::<Vec<_>>(), hands);
+    drop(game);
+    
+    // Once it passes, the absent player forfeits, then the game is reset and the seat reopened
+    let b_id = server.room().await.game_state.lock().await.players[1].id;
+    let game_over = expect(&mut b, "GameOver").await;
+    assert_eq!(game_over["outcome"], json!({ "Win": b_id }));
+    let view = expect(&mut b, "GameState").await;
+    assert_eq!(view["game_started"], false);
+    assert_eq!(view["paused"], false);
+    assert_eq!(server.room().await.game_state.lock().await.players.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rules_are_sent_at_the_start_without_the_seed() {
+    let game_config = GameConfig {
+        seed: Some(42),
+        hand_size: 4,
+        max_hand_size: 4,
+        sudden_death: true,
+        max_redeals: 2,
+        ..GameConfig::default()
+    };
+    let server = TestServer::with_game(ServerConfig::default(), game_config.clone());
+    let mut a = server.connect().await;
+    let mut b = server.connect().await;
+    
+    for ws in [&mut a, &mut b] {
+        let rules = expect(ws, "GameConfig").await["config"].clone();
+        assert_eq!((rules["hand_size"].as_u64(), rules["draw_pile_size"].as_u64()), (Some(4), Some(15)));
+        assert_eq!(rules["sudden_death"], true);
+        assert_eq!(rules["max_redeals"], 2);
+        assert_eq!(rules["suit_rule"], serde_json::to_value(game_config.suit_rule).unwrap());
+        assert!(rules["seed"].is_null());
+        
+        // The rules come before the first state
+        assert_eq!(next_message(ws).await["type"], "GameState");
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn views_carry_as_many_pile_cards_as_asked_for() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    let pile: Vec<Card> = ["2S", "3S", "4S", "5S", "6S"].iter().map(|name| name.parse().unwrap()).collect();
+    server.room().await.game_state.lock().await.center_piles = vec![pile.clone(), vec![pile[0]]];
+    
+    // Each pile keeps its top cards, up to the depth; shorter piles are sent whole
+    for (depth, sent) in [(1, &pile[4..]), (3, &pile[2..]), (10, &pile[..])] {
+        send(&mut a, json!({ "SetViewOptions": { "center_pile_depth": depth } })).await;
+        let view = expect(&mut a, "GameState").await;
+        assert_eq!(view["center_piles"][0], serde_json::to_value(sent).unwrap());
+        assert_eq!(view["center_piles"][1], serde_json::to_value(&pile[..1]).unwrap());
+    }
+    
+    // A depth of zero would hide the tops, so it counts as one
+    send(&mut a, json!({ "SetViewOptions": { "center_pile_depth": 0 } })).await;
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(view["center_piles"][0], serde_json::to_value(&pile[4..]).unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn idle_players_are_sent_their_state_again() {
+    let interval = Duration::from_secs(10);
+    let server = TestServer::new(ServerConfig {
+        idle_resend_interval: Some(interval),
+        ..ServerConfig::default()
+    });
+    let [mut a, mut b] = server.start_game().await;
+    
+    // Nothing is resent while the game is active
+    tokio::time::sleep(interval / 2).await;
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    expect(&mut a, "GameState").await;
+    expect(&mut b, "GameState").await;
+    tokio::time::sleep(interval / 2).await;
+    expect_nothing_pending(&mut a).await;
+    
+    // Once nobody has acted for the interval, both players get their state again
+    tokio::time::sleep(interval).await;
+    for ws in [&mut a, &mut b] {
+        let view = next_message(ws).await;
+        assert_eq!(view["type"], "GameState");
+        assert_eq!(view["game_started"], true);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn game_start_that_cannot_reach_a_player_pauses_for_the_other() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    settle().await;
+    let a_id = *server.room().await.players.lock().await.keys().next().unwrap();
+    let mut b = server.connect().await;
+    settle().await;
+    let b_id = *server.room().await.players.lock().await.keys().find(|&&id| id != a_id).unwrap();
+    
+    // B's connection stops taking frames just before the game is dealt
+    server.room().await.players.lock().await.get_mut(&b_id).unwrap().close();
+    
+    expect(&mut a, "GameConfig").await;
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(view["player_id"], json!(a_id));
+    while let Some(Ok(frame)) = b.next().await {
+        if let Message::Text(text) = frame {
+            assert!(!text.contains("GameConfig") && !text.contains("GameState"), "B was sent {}", text);
+        }
+    }
+    
+    // A is left with a paused game, as after any disconnect
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(view["paused"], true);
+    assert_eq!(server.room().await.game_state.lock().await.phase(), GamePhase::Paused);
+}
+
+#[tokio::test(start_paused = true)]
+async fn room_password_is_needed_to_join_or_watch() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "CreateRoom": { "password": "open sesame" } })).await;
+    let code = expect(&mut a, "RoomJoined").await["code"].as_str().unwrap().to_string();
+    
+    let mut b = server.connect().await;
+    for password in [Value::Null, json!("open")] {
+        send(&mut b, json!({ "JoinRoom": { "code": code, "password": password } })).await;
+        assert_eq!(expect(&mut b, "Error").await["code"], "wrong_password");
+    }
+    send(&mut b, json!({ "JoinRoom": { "code": code, "password": "open sesame" } })).await;
+    assert_eq!(expect(&mut b, "RoomJoined").await["code"], json!(code));
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    
+    let mut c = server.connect().await;
+    send(&mut c, json!({ "Spectate": { "code": code } })).await;
+    assert_eq!(expect(&mut c, "Error").await["code"], "wrong_password");
+    send(&mut c, json!({ "Spectate": { "code": code, "password": "open sesame" } })).await;
+    expect(&mut c, "SpectatorState").await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn end_only_spectators_are_only_shown_the_result() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "CreateRoom": { "spectators": "EndOnly" } })).await;
+    let code = expect(&mut a, "RoomJoined").await["code"].as_str().unwrap().to_string();
+    let mut b = server.connect().await;
+    send(&mut b, json!({ "JoinRoom": { "code": code } })).await;
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    
+    let mut c = server.connect().await;
+    send(&mut c, json!({ "Spectate": { "code": code } })).await;
+    send(&mut c, json!({ "Ping": { "nonce": 0 } })).await;
+    expect(&mut c, "Pong").await;
+    
+    // The winning play isn't shown, but its result is
+    win_with_last_card(&server, &mut a).await;
+    loop {
+        let message = next_message(&mut c).await;
+        assert_ne!(message["type"], "SpectatorState");
+        if message["type"] == "GameOver" {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn unresponsive_webhook_does_not_hold_up_the_game() {
+    // An endpoint that takes connections but never answers them
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (connections, mut accepted) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let _ = connections.send(stream);
+        }
+    });
+    
+    let server = TestServer::new(ServerConfig {
+        outcome_webhook: Some(url),
+        ..ServerConfig::default()
+    });
+    let [mut a, _b] = server.start_game().await;
+    
+    // The win is announced while the post is still waiting for an answer
+    let (a_id, game_over) = win_with_last_card(&server, &mut a).await;
+    assert_eq!(game_over["outcome"], json!({ "Win": a_id }));
+    let _held = accepted.recv().await.unwrap();
+    expect_nothing_pending(&mut a).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn broadcasts_count_towards_the_bytes_sent() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    let before = server.metrics.snapshot();
+    assert!(before.bytes_sent > 0);
+    
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    let mut views = Vec::new();
+    for ws in [&mut a, &mut b] {
+        views.push(expect(ws, "GameState").await.to_string().len() as u64);
+    }
+    
+    // At least both views went out, and a view is bigger than the average message
+    let after = server.metrics.snapshot();
+    assert!(after.bytes_sent >= before.bytes_sent + views.iter().sum::<u64>());
+    assert!(after.messages_sent >= before.messages_sent + 2);
+    assert!(after.average_message_bytes > 0.0 && after.average_message_bytes < views[0] as f64);
+}
+
+// Adds a hint to the views of one chosen player
+#[derive(Debug, Default)]
+struct HintProjector {
+    target: std::sync::Mutex<Option<Uuid>>,
+}
+
+impl ViewProjector for HintProjector {
+    fn project(&self, player_id: Uuid, view: &mut PlayerView) {
+        if *self.target.lock().unwrap() == Some(player_id) {
+            view.extras.insert("hint".to_string(), json!("play the queen"));
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn projected_fields_reach_only_the_intended_player() {
+    let projector = Arc::new(HintProjector::default());
+    let server = TestServer::new(ServerConfig { view_projector: projector.clone(), ..ServerConfig::default() });
+    let [mut a, mut b] = server.start_game().await;
+    let a_id = server.room().await.game_state.lock().await.players[0].id;
+    *projector.target.lock().unwrap() = Some(a_id);
+    
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    assert_eq!(expect(&mut a, "GameState").await["hint"], "play the queen");
+    let view = expect(&mut b, "GameState").await;
+    assert!(view.get("hint").is_none());
+    assert!(view.get("extras").is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn each_connection_chooses_how_ambiguous_plays_resolve() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    send(&mut a, json!({ "SetPileSelection": { "mode": "Ask" } })).await;
+    {
+        let room = server.room().await;
+        let mut game = room.game_state.lock().await;
+        game.center_piles = vec![vec!["4S".parse().unwrap()], vec!["6D".parse().unwrap()]];
+        for player in game.players.iter_mut() {
+            player.hand[0] = "5H".parse().unwrap();
+        }
+    }
+    
+    let play = json!({ "PlayCard": { "card_index": 0, "pile_index": null } });
+    send(&mut a, play.clone()).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "ambiguous_pile");
+    
+    // The opponent kept the default and has the first pile chosen for them
+    send(&mut b, play).await;
+    assert_eq!(expect(&mut b, "Ack").await["pile_index"], 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_player_who_stops_reading_holds_up_nobody() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, _b] = server.start_game().await;
+    let b_id = server.room().await.game_state.lock().await.players[1].id;
+    
+    // Swap the opponent's connection for one whose queue is never drained
+    let (stalled, mut stalled_queue) = tokio::sync::mpsc::channel(1);
+    let slow = Connection::new(stalled, None, server.metrics.clone(), Arc::new(IdentityProjector));
+    server.room().await.players.lock().await.insert(b_id, slow);
+    
+    // Each rejected play is still relayed to the opponent as activity
+    for _ in 0..5 {
+        send(&mut a, json!({ "PlayCard": { "card_index": 99, "pile_index": null } })).await;
+        expect(&mut a, "Error").await;
+    }
+    send(&mut a, json!("RequestNewCenterCards")).await;
+    expect(&mut a, "Ack").await;
+    expect(&mut a, "GameState").await;
+    
+    // The stalled player kept only what fit in their queue; the rest was dropped
+    assert!(stalled_queue.try_recv().is_ok());
+    assert!(stalled_queue.try_recv().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn connecting_playing_and_leaving_at_once_never_deadlocks() {
+    let server = Arc::new(TestServer::new(ServerConfig {
+        start_debounce: Duration::ZERO,
+        disconnect_grace: Duration::from_millis(5),
+        ..ServerConfig::default()
+    }));
+    let actions = [
+        json!({ "PlayCard": { "card_index": 0, "pile_index": null } }),
+        json!("RequestNewCenterCards"),
+        json!({ "ClearPile": { "pile_index": 0 } }),
+        json!("GetStatus"),
+        json!("RequestRedeal"),
+    ];
+    
+    // Players come and go while others are starting the game and playing it
+    let mut tasks = Vec::new();
+    for n in 0..32 {
+        let server = server.clone();
+        let actions = actions.clone();
+        tasks.push(tokio::spawn(async move {
+            for round in 0..4 {
+                let mut ws = server.connect().await;
+                for action in actions.iter().take(1 + (n + round) % actions.len()) {
+                    send(&mut ws, action.clone()).await;
+                }
+                if (n + round) % 3 == 0 {
+                    tokio::task::yield_now().await;
+                    let _ = ws.close(None).await;
+                }
+            }
+        }));
+    }
+    let finished = tokio::time::timeout(Duration::from_secs(30), async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Let the server see every player off and give up their held seats
+        while server.metrics.snapshot().active_connections > 0 {
+            settle().await;
+        }
+        tokio::time::sleep(server.config.disconnect_grace * 2).await;
+        
+        // Every lock can still be taken, in the documented order
+        for room in server.rooms.all().await {
+            let _players = room.players.lock().await;
+            let _game = room.game_state.lock().await;
+        }
+    });
+    assert!(finished.await.is_ok(), "connections deadlocked");
+    
+    // And the server still deals new games
+    let [mut a, _b] = tokio::time::timeout(Duration::from_secs(30), server.start_game())
+        .await
+        .expect("a new game couldn't start");
+    send(&mut a, json!("GetStatus")).await;
+    expect(&mut a, "Status").await;
+}
+
+#[test]
+fn client_versions_compare_part_by_part() {
+    assert!(version_below("1.2.9", "1.10.0"));
+    assert!(version_below("v0.9", "1"));
+    assert!(!version_below("1.10", "1.10.0"));
+    assert!(!version_below("2.0.0-beta", "1.10.0"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn outdated_clients_are_told_to_upgrade_and_let_go() {
+    let server = TestServer::new(ServerConfig {
+        min_client_version: Some("1.10.0".to_string()),
+        client_download_url: Some("https://example.com/speed".to_string()),
+        ..ServerConfig::default()
+    });
+    
+    let mut old = server.connect().await;
+    send(&mut old, json!({ "Hello": { "client_version": "1.2.9" } })).await;
+    let notice = expect(&mut old, "UpgradeRequired").await;
+    assert_eq!(notice["min_version"], "1.10.0");
+    assert_eq!(notice["download_url"], "https://example.com/speed");
+    loop {
+        match tokio::time::timeout(RECEIVE_TIMEOUT, old.next()).await.expect("connection left open") {
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => {}
+        }
+    }
+    
+    // A current client carries on as usual
+    let mut current = server.connect().await;
+    send(&mut current, json!({ "Hello": { "client_version": "1.10.0" } })).await;
+    send(&mut current, json!({ "Ping": { "nonce": 7 } })).await;
+    assert_eq!(expect(&mut current, "Pong").await["nonce"], 7);
+    assert_eq!(server.metrics.snapshot().active_connections, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn finished_games_are_followed_by_a_rematch_after_the_countdown() {
+    let countdown = Duration::from_secs(3);
+    let server = TestServer::with_game(
+        ServerConfig { auto_rematch: Some(countdown), ..ServerConfig::default() },
+        GameConfig { max_moves: Some(1), ..GameConfig::default() },
+    );
+    let [mut a, mut b] = server.start_game().await;
+    let (a_id, b_id) = {
+        let room = server.room().await;
+        let game = room.game_state.lock().await;
+        (game.players[0].id, game.players[1].id)
+    };
+    
+    // Each game ends on its first move, and the next is dealt to the same players on its own
+    for _ in 0..2 {
+        send(&mut a, json!("RequestNewCenterCards")).await;
+        for ws in [&mut a, &mut b] {
+            expect(ws, "GameOver").await;
+            assert_eq!(expect(ws, "RematchScheduled").await["delay_ms"], 3000);
+        }
+        
+        tokio::time::sleep(countdown - Duration::from_millis(10)).await;
+        assert_eq!(server.room().await.game_state.lock().await.phase(), GamePhase::Over);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        for (ws, id) in [(&mut a, a_id), (&mut b, b_id)] {
+            let view = loop {
+                let view = expect(ws, "GameState").await;
+                if view["game_started"] == true && view["outcome"].is_null() {
+                    break view;
+                }
+            };
+            assert_eq!(view["player_id"], json!(id));
+        }
+        assert_eq!(server.room().await.game_state.lock().await.phase(), GamePhase::Playing);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn each_pair_of_players_gets_a_game_of_their_own() {
+    let server = TestServer::new(ServerConfig::default());
+    let [_a, _b] = server.start_game().await;
+    let [_c, _d] = server.start_game().await;
+    
+    let rooms = server.rooms.all().await;
+    assert_eq!(rooms.len(), 2);
+    for room in rooms {
+        assert_eq!(room.game_state.lock().await.phase(), GamePhase::Playing);
+        assert_eq!(room.players.lock().await.len(), 2);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn friends_meet_in_a_private_room_by_its_code() {
+    let server = TestServer::new(ServerConfig {
+        require_ready: true,
+        ..ServerConfig::default()
+    });
+    let mut a = server.connect().await;
+    send(&mut a, json!("CreateRoom")).await;
+    let code = expect(&mut a, "RoomJoined").await["code"].as_str().unwrap().to_string();
+    assert_eq!(code.len(), 5);
+    assert_eq!(expect(&mut a, "GameState").await["game_started"], false);
+    assert_eq!(expect(&mut a, "Lobby").await["players"][0]["ready"], true);
+    
+    // A stranger isn't matched with a player waiting in a private room, nor let in on a wrong code
+    let mut b = server.connect().await;
+    send(&mut b, json!({ "JoinRoom": { "code": "NOPE0" } })).await;
+    assert_eq!(expect(&mut b, "Error").await["code"], "room_not_found");
+    expect_nothing_pending(&mut a).await;
+    
+    send(&mut b, json!({ "JoinRoom": { "code": code } })).await;
+    assert_eq!(expect(&mut b, "RoomJoined").await["code"], json!(code));
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+}
+
+#[tokio::test(start_paused = true)]
+async fn players_queueing_for_a_match_are_seated_together() {
+    let server = TestServer::new(ServerConfig {
+        match_on_connect: false,
+        ..ServerConfig::default()
+    });
+    let mut a = server.connect().await;
+    let mut b = server.connect().await;
+    assert_eq!(server.rooms.all().await.len(), 2);
+    
+    send(&mut a, json!("QueueForMatch")).await;
+    expect(&mut a, "Queued").await;
+    send(&mut b, json!("QueueForMatch")).await;
+    expect(&mut a, "MatchFound").await;
+    expect(&mut b, "MatchFound").await;
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    
+    // The private rooms they started in were given up
+    let rooms = server.rooms.all().await;
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].players.lock().await.len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropped_player_takes_their_seat_back_with_the_session_token() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    let a_token = token(&mut a).await;
+    let mut b = server.connect().await;
+    expect(&mut a, "GameState").await;
+    let view = expect(&mut b, "GameState").await;
+    assert_eq!(view["game_started"], true);
+    let a_id = server.room().await.game_state.lock().await.players[0].id;
+    let hand = server.room().await.game_state.lock().await.players[0].hand.clone();
+    
+    drop(a);
+    assert_eq!(expect(&mut b, "GameState").await["paused"], true);
+    
+    // A stranger can't use a made-up token, and the real one gets the same seat and cards back
+    let mut c = server.connect().await;
+    send(&mut c, json!({ "Reconnect": { "token": "made-up" } })).await;
+    assert_eq!(expect(&mut c, "Error").await["code"], "session_not_found");
+    let mut a = server.connect().await;
+    token(&mut a).await;
+    send(&mut a, json!({ "Reconnect": { "token": a_token } })).await;
+    let new_token = token(&mut a).await;
+    assert_ne!(new_token, a_token);
+    expect(&mut a, "GameConfig").await;
+    let view = expect(&mut a, "GameState").await;
+    assert_eq!(view["player_id"], json!(a_id));
+    assert_eq!(view["paused"], false);
+    assert_eq!(serde_json::from_value::<Vec<Card>>(view["hand"].clone()).unwrap(), hand);
+    while expect(&mut b, "GameState").await["paused"] == true {}
+    
+    // Each token is good for one reconnect, so a leaked one can't be used to take the seat again
+    drop(a);
+    assert_eq!(expect(&mut b, "GameState").await["paused"], true);
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "Reconnect": { "token": a_token } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "session_not_found");
+    send(&mut a, json!({ "Reconnect": { "token": new_token } })).await;
+    expect(&mut a, "GameConfig").await;
+    assert_eq!(expect(&mut a, "GameState").await["player_id"], json!(a_id));
+}
+
+#[tokio::test(start_paused = true)]
+async fn session_tokens_expire() {
+    let server = TestServer::new(ServerConfig {
+        session_ttl: Some(Duration::from_millis(500)),
+        ..ServerConfig::default()
+    });
+    let [(a, a_id, a_token), _b] = server.start_game_with_sessions().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    drop(a);
+    settle().await;
+    assert_eq!(server.phase_of(a_id).await, GamePhase::Paused);
+    
+    // The seat is still held for the grace period, but not for this token
+    let mut a = server.connect().await;
+    token(&mut a).await;
+    send(&mut a, json!({ "Reconnect": { "token": a_token } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "session_not_found");
+}
+
+#[tokio::test(start_paused = true)]
+async fn keyed_action_retried_after_reconnecting_is_applied_once() {
+    let server = TestServer::new(ServerConfig::default());
+    let [(mut a, a_id, a_token), (mut b, _, _)] = server.start_game_with_sessions().await;
+    {
+        let room = server.room_of(a_id).await;
+        let mut game = room.game_state.lock().await;
+        game.center_piles = vec![vec!["4S".parse().unwrap()], vec!["KC".parse().unwrap()]];
+        for player in game.players.iter_mut() {
+            player.hand[0] = "5H".parse().unwrap();
+        }
+    }
+    
+    let play = json!({ "KeyedAction": {
+        "action": { "PlayCard": { "card_index": 0 } },
+        "idempotency_key": "play-1",
+    } });
+    send(&mut a, play.clone()).await;
+    let ack = expect(&mut a, "Ack").await;
+    
+    // The ack was lost with the connection, so the client retries on the new one
+    drop(a);
+    while expect(&mut b, "GameState").await["paused"] == false {}
+    let mut a = server.connect().await;
+    token(&mut a).await;
+    send(&mut a, json!({ "Reconnect": { "token": a_token } })).await;
+    token(&mut a).await;
+    expect(&mut a, "GameState").await;
+    send(&mut a, play).await;
+    assert_eq!(expect(&mut a, "Ack").await, ack);
+    assert_eq!(server.room_of(a_id).await.game_state.lock().await.center_piles[0].len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn chosen_names_are_shown_to_the_opponent() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "SetName": { "name": "   " } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "invalid_name");
+    send(&mut a, json!({ "SetName": { "name": "  Ann " } })).await;
+    let view = expect(&mut a, "GameState").await;
+    assert!(view["opponent_name"].is_null());
+    
+    // The name is carried into the game the player is dealt
+    let mut b = server.connect().await;
+    let view = loop {
+        let view = expect(&mut b, "GameState").await;
+        if view["game_started"] == true {
+            break view;
+        }
+    };
+    assert_eq!(view["opponent_name"], "Ann");
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    
+    // Once dealt in, players keep the names they started with
+    send(&mut a, json!({ "SetName": { "name": "Bea" } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "action_not_allowed_in_phase");
+}
+
+#[tokio::test(start_paused = true)]
+async fn chat_reaches_everyone_in_the_room() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        ..ServerConfig::default()
+    });
+    let [mut a, mut b] = server.start_game().await;
+    let mut watcher = server.connect().await;
+    expect(&mut watcher, "SpectatorState").await;
+    
+    send(&mut a, json!({ "Chat": { "text": " good\nluck " } })).await;
+    for ws in [&mut a, &mut b, &mut watcher] {
+        let chat = expect(ws, "Chat").await;
+        assert_eq!(chat["text"], "good luck");
+        assert_eq!(chat["spectator"], false);
+    }
+    
+    // Spectators may chat too, marked as such
+    send(&mut watcher, json!({ "Chat": { "text": "nice" } })).await;
+    assert_eq!(expect(&mut b, "Chat").await["spectator"], true);
+    
+    send(&mut a, json!({ "Chat": { "text": "x".repeat(201) } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "invalid_chat");
+}
+
+#[tokio::test(start_paused = true)]
+async fn spectator_chat_only_reaches_spectators() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        ..ServerConfig::default()
+    });
+    let [mut a, mut b] = server.start_game().await;
+    let mut first = server.connect().await;
+    expect(&mut first, "SpectatorState").await;
+    let mut second = server.connect().await;
+    expect(&mut second, "SpectatorState").await;
+    
+    send(&mut first, json!({ "SpectatorChat": { "text": " who wins?\n" } })).await;
+    for ws in [&mut first, &mut second] {
+        assert_eq!(expect(ws, "SpectatorChat").await["text"], "who wins?");
+    }
+    for ws in [&mut a, &mut b] {
+        send(ws, json!({ "Ping": { "nonce": 0 } })).await;
+        loop {
+            let message = next_message(ws).await;
+            assert_ne!(message["type"], "SpectatorChat");
+            if message["type"] == "Pong" {
+                break;
+            }
+        }
+    }
+    
+    // Players can't talk on the spectators' channel
+    send(&mut a, json!({ "SpectatorChat": { "text": "me!" } })).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "not_spectating");
+    send(&mut first, json!({ "SpectatorChat": { "text": " " } })).await;
+    assert_eq!(expect(&mut first, "Error").await["code"], "invalid_chat");
+}
+
+#[tokio::test(start_paused = true)]
+async fn open_seat_goes_to_the_longest_watching_spectator_who_wants_it() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        promote_spectators: true,
+        ..ServerConfig::default()
+    });
+    let [a, mut b] = server.start_game().await;
+    let mut first = server.connect().await;
+    expect(&mut first, "SpectatorState").await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let mut second = server.connect().await;
+    expect(&mut second, "SpectatorState").await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let mut third = server.connect().await;
+    expect(&mut third, "SpectatorState").await;
+    
+    // The first spectator would rather keep watching
+    send(&mut first, json!("DeclinePromotion")).await;
+    send(&mut first, json!({ "Ping": { "nonce": 0 } })).await;
+    expect(&mut first, "Pong").await;
+    
+    // Once A forfeits, the seat goes to the second spectator and a new game is dealt to B and them
+    drop(a);
+    assert_eq!(expect(&mut second, "Promoted").await["seat"], 0);
+    while expect(&mut second, "GameState").await["game_started"] == false {}
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    for ws in [&mut first, &mut third] {
+        assert_eq!(expect(ws, "SpectatorState").await["game_started"], true);
+    }
+    
+    // The promoted player can play, and a player can't decline a promotion
+    send(&mut second, json!("RequestNewCenterCards")).await;
+    let reply = loop {
+        let message = next_message(&mut second).await;
+        if message["type"] == "Ack" || message["type"] == "Error" {
+            break message;
+        }
+    };
+    assert_ne!(reply["code"], "spectating");
+    send(&mut b, json!("DeclinePromotion")).await;
+    assert_eq!(expect(&mut b, "Error").await["code"], "not_spectating");
+}
+
+// The hand of a seated player, straight from the game
+async fn hand_of(server: &TestServer, player_id: Uuid) -> Vec<Card> {
+    let room = server.room_of(player_id).await;
+    let game = room.game_state.lock().await;
+    game.players.iter().find(|p| p.id == player_id).unwrap().hand.clone()
+}
+
+#[tokio::test(start_paused = true)]
+async fn fresh_game_handoff_deals_the_promoted_spectator_a_new_game() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        promote_spectators: true,
+        ..ServerConfig::default()
+    });
+    let [(a, a_id, _), (mut b, b_id, _)] = server.start_game_with_sessions().await;
+    let mut c = server.connect().await;
+    expect(&mut c, "SpectatorState").await;
+    let hand = hand_of(&server, a_id).await;
+    
+    // A forfeits the game they left, and C only joins the next one
+    drop(a);
+    assert_eq!(expect(&mut b, "GameOver").await["outcome"], json!({ "Win": b_id }));
+    expect(&mut c, "Promoted").await;
+    let view = loop {
+        let view = expect(&mut c, "GameState").await;
+        if view["game_started"] == true {
+            break view;
+        }
+    };
+    assert_ne!(serde_json::from_value::<Vec<Card>>(view["hand"].clone()).unwrap(), hand);
+}
+
+#[tokio::test(start_paused = true)]
+async fn continue_game_handoff_gives_the_spectator_the_departed_players_cards() {
+    let server = TestServer::new(ServerConfig {
+        max_rooms: Some(1),
+        promote_spectators: true,
+        spectator_handoff: SpectatorHandoff::ContinueGame,
+        ..ServerConfig::default()
+    });
+    let [(a, a_id, _), (mut b, _, _)] = server.start_game_with_sessions().await;
+    let mut c = server.connect().await;
+    expect(&mut c, "SpectatorState").await;
+    let hand = hand_of(&server, a_id).await;
+    
+    drop(a);
+    assert_eq!(expect(&mut c, "Promoted").await["seat"], 0);
+    expect(&mut c, "GameConfig").await;
+    let view = expect(&mut c, "GameState").await;
+    assert_eq!(view["paused"], false);
+    assert_eq!(serde_json::from_value::<Vec<Card>>(view["hand"].clone()).unwrap(), hand);
+    
+    // B's game carries on instead of ending
+    while expect(&mut b, "GameState").await["paused"] == false {}
+    loop {
+        let message = next_message(&mut b).await;
+        assert_ne!(message["type"], "GameOver");
+        if message["type"] == "GameState" && message["paused"] == false {
+            break;
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn game_is_dealt_once_both_players_are_ready() {
+    let server = TestServer::new(ServerConfig {
+        require_ready: true,
+        ..ServerConfig::default()
+    });
+    let start_debounce = server.config.start_debounce;
+    let mut a = server.try_connect("ws://localhost/", None).await.unwrap();
+    let mut b = server.try_connect("ws://localhost/", None).await.unwrap();
+    let lobby = expect(&mut b, "Lobby").await;
+    assert_eq!(lobby["players"].as_array().unwrap().len(), 2);
+    assert!(lobby["players"].as_array().unwrap().iter().all(|p| p["ready"] == false));
+    
+    // One ready player isn't enough
+    send(&mut a, json!("Ready")).await;
+    let lobby = expect(&mut b, "Lobby").await;
+    assert_eq!(lobby["players"].as_array().unwrap().iter().filter(|p| p["ready"] == true).count(), 1);
+    tokio::time::sleep(start_debounce * 2).await;
+    assert_eq!(server.room().await.game_state.lock().await.phase(), GamePhase::Ready);
+    
+    send(&mut b, json!("Ready")).await;
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    
+    // There's nothing left to get ready for once the game is dealt
+    send(&mut a, json!("Ready")).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "action_not_allowed_in_phase");
+}
+
+#[tokio::test(start_paused = true)]
+async fn rematch_is_dealt_once_both_players_ask() {
+    let server = TestServer::new(ServerConfig::default());
+    let [mut a, mut b] = server.start_game().await;
+    send(&mut a, json!("RequestRematch")).await;
+    assert_eq!(expect(&mut a, "Error").await["code"], "action_not_allowed_in_phase");
+    
+    let (a_id, _) = win_with_last_card(&server, &mut a).await;
+    expect(&mut b, "GameOver").await;
+    send(&mut a, json!("RequestRematch")).await;
+    for ws in [&mut a, &mut b] {
+        assert_eq!(expect(ws, "RematchOffered").await["player_id"], json!(a_id));
+    }
+    
+    send(&mut b, json!("RequestRematch")).await;
+    for ws in [&mut a, &mut b] {
+        let view = loop {
+            let view = expect(ws, "GameState").await;
+            if view["game_started"] == true && view["outcome"].is_null() {
+                break view;
+            }
+        };
+        assert_eq!(view["hand"].as_array().unwrap().len(), 5);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn match_rounds_are_dealt_until_someone_wins_the_match() {
+    let server = TestServer::new(ServerConfig::default());
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "CreateMatch": { "best_of": 3 } })).await;
+    let code = expect(&mut a, "RoomJoined").await["code"].clone();
+    let mut b = server.connect().await;
+    send(&mut b, json!({ "JoinRoom": { "code": code } })).await;
+    while expect(&mut a, "GameState").await["game_started"] == false {}
+    while expect(&mut b, "GameState").await["game_started"] == false {}
+    
+    for round in 1..=2 {
+        let (a_id, _) = win_with_last_card(&server, &mut a).await;
+        let standings = expect(&mut b, "RoundOver").await;
+        assert_eq!(standings["series"]["round"], round);
+        assert_eq!(standings, expect(&mut a, "RoundOver").await);
+        if round == 2 {
+            assert_eq!(standings["match_winner"], json!(a_id));
+            break;
+        }
+        assert!(standings["match_winner"].is_null());
+        
+        // The next round is dealt after the break
+        for ws in [&mut a, &mut b] {
+            loop {
+                let view = expect(ws, "GameState").await;
+                if view["game_started"] == true && view["outcome"].is_null() {
+                    break;
+                }
+            }
+        }
+        assert_eq!(server.playing_room().await.game_state.lock().await.series().round, 2);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn stale_grace_timer_leaves_the_opponent_their_own_grace_period() {
+    let server = TestServer::new(ServerConfig::default());
+    let grace = server.config.disconnect_grace;
+    let [(a, a_id, a_token), (b, b_id, _)] = server.start_game_with_sessions().await;
+    
+    // A drops and comes back halfway through their grace period
+    drop(a);
+    settle().await;
+    assert_eq!(server.phase_of(a_id).await, GamePhase::Paused);
+    tokio::time::sleep(grace / 4).await;
+    let mut a = server.connect().await;
+    send(&mut a, json!({ "Reconnect": { "token": a_token } })).await;
+    settle().await;
+    assert_eq!(server.phase_of(a_id).await, GamePhase::Playing);
+    
+    // Then B drops before A's old timer runs out
+    tokio::time::sleep(grace / 4).await;
+    drop(b);
+    settle().await;
+    assert_eq!(server.phase_of(a_id).await, GamePhase::Paused);
+    
+    // A's timer passing leaves the game paused for B
+    tokio::time::sleep(grace / 2 + Duration::from_millis(10)).await;
+    let room = server.room_of(b_id).await;
+    assert_eq!(room.game_state.lock().await.phase(), GamePhase::Paused);
+    
+    // B's own timer forfeits the game to A
+    tokio::time::sleep(grace / 2).await;
+    let game_over = expect(&mut a, "GameOver").await;
+    assert_eq!(game_over["outcome"], json!({ "Win": a_id }));
+}
+
+#[tokio::test(start_paused = true)]
+async fn forfeit_result_is_shown_before_the_game_is_reset() {
+    let server = TestServer::new(ServerConfig::default());
+    let config = server.config.clone();
+    let [(mut a, a_id, _), (b, b_id, _)] = server.start_game_with_sessions().await;
+    
+    drop(b);
+    tokio::time::sleep(config.disconnect_grace + Duration::from_millis(10)).await;
+    let game_over = expect(&mut a, "GameOver").await;
+    assert_eq!(game_over["outcome"], json!({ "Win": a_id }));
+    
+    // The won game stays up for the round break
+    tokio::time::sleep(config.round_break - Duration::from_millis(20)).await;
+    let room = server.room_of(b_id).await;
+    assert_eq!(room.game_state.lock().await.phase(), GamePhase::Over);
+    
+    // Then the seat reopens with the winner kept in theirs
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let game = room.game_state.lock().await;
+    assert_eq!(game.phase(), GamePhase::Waiting);
+    assert_eq!(game.players.iter().map(|p| p.id).collect::<Vec<_>>(), vec![a_id]);
+}